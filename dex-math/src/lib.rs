@@ -0,0 +1,263 @@
+//! Pricing math of the DEX pallet.
+//!
+//! The pallet uses these functions for every swap, deposit and withdrawal, so clients that
+//! depend on this crate get exactly the same results as the runtime.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+// Products of two balances overflow the balance type long before the results do,
+// so intermediate values are kept in `U256` (or `U512` for products of three values)
+// and converted back only when the result has to fit into a balance
+//
+// Every division has an explicit rounding direction, and it is always chosen in favour
+// of the pool: amounts paid to users are rounded down, amounts paid by users and pool
+// shares burned are rounded up, pool shares minted are rounded down
+//
+// `x` is the pool balance of the asset that the user gives (or names), `y` is the pool
+// balance of the other asset and `constant` is the product of the balances stored in the pool
+
+#[cfg(test)]
+mod test;
+
+use core::cmp::Ordering;
+use sp_arithmetic::{traits::AtLeast32BitUnsigned, PerThing, Permill};
+use sp_core::{U256, U512};
+
+// Returns `None` for balances that do not fit into `u128`
+pub fn to_u256<B: AtLeast32BitUnsigned>(value: B) -> Option<U256> {
+    let value: u128 = value.try_into().ok()?;
+    Some(U256::from(value))
+}
+
+// Returns `None` if the value does not fit into the balance type
+pub fn from_u256<B: AtLeast32BitUnsigned>(value: U256) -> Option<B> {
+    let value: u128 = value.try_into().ok()?;
+    value.try_into().ok()
+}
+
+// a * b, it can not overflow for balances up to `u128`
+pub fn mul<B: AtLeast32BitUnsigned>(a: B, b: B) -> Option<U256> {
+    to_u256(a)?.checked_mul(to_u256(b)?)
+}
+
+// a * b / c rounded down
+pub fn mul_div_floor<B: AtLeast32BitUnsigned>(a: B, b: B, c: B) -> Option<B> {
+    let c = to_u256(c)?;
+    if c.is_zero() {
+        return None;
+    }
+    from_u256(mul(a, b)? / c)
+}
+
+// a * b / c rounded up
+pub fn mul_div_ceil<B: AtLeast32BitUnsigned>(a: B, b: B, c: B) -> Option<B> {
+    let c = to_u256(c)?;
+    if c.is_zero() {
+        return None;
+    }
+    from_u256(div_ceil(mul(a, b)?, c)?)
+}
+
+// a / b rounded up
+pub fn div_ceil(a: U256, b: U256) -> Option<U256> {
+    if b.is_zero() {
+        return None;
+    }
+    let (quotient, remainder) = a.div_mod(b);
+    if remainder.is_zero() {
+        Some(quotient)
+    } else {
+        quotient.checked_add(U256::one())
+    }
+}
+
+// Compares `part` of `a` with `b` without rounding
+pub fn cmp_part(a: U256, part: Permill, b: U256) -> Ordering {
+    a.full_mul(U256::from(part.deconstruct()))
+        .cmp(&b.full_mul(U256::from(Permill::ACCURACY)))
+}
+
+// numerator / denominator as `Permill`, rounded the same way as `Permill::from_rational`
+// Values that do not fit into `u128` are scaled down, which keeps the ratio
+pub fn ratio(numerator: U256, denominator: U256) -> Permill {
+    let shift = numerator.bits().max(denominator.bits()).saturating_sub(128);
+    Permill::from_rational(
+        (numerator >> shift).low_u128(),
+        (denominator >> shift).low_u128(),
+    )
+}
+
+// The output of a constant product swap of `amount` before the fee, y - k / (x + amount)
+// The new balance of the pool is rounded up, so the product of balances never decreases
+pub fn swap_output<B: AtLeast32BitUnsigned>(
+    constant: U256,
+    pool_origin_token_balance: B,
+    pool_dest_token_balance: B,
+    amount: B,
+) -> Option<B> {
+    let new_origin_token_balance = to_u256(pool_origin_token_balance.checked_add(&amount)?)?;
+    let new_dest_token_balance: B = from_u256(div_ceil(constant, new_origin_token_balance)?)?;
+    pool_dest_token_balance.checked_sub(&new_dest_token_balance)
+}
+
+// The output of swapping `amount` after the `fee`, which is taken from the output rounded up
+pub fn quote_out<B: AtLeast32BitUnsigned>(
+    constant: U256,
+    pool_origin_token_balance: B,
+    pool_dest_token_balance: B,
+    amount: B,
+    fee: Permill,
+) -> Option<B> {
+    let output = swap_output(
+        constant,
+        pool_origin_token_balance,
+        pool_dest_token_balance,
+        amount,
+    )?;
+    let fee_amount = fee.mul_ceil(output.clone());
+    output.checked_sub(&fee_amount)
+}
+
+// The smallest amount to swap for which `quote_out` gives at least `amount_out`
+pub fn quote_in<B: AtLeast32BitUnsigned>(
+    constant: U256,
+    pool_origin_token_balance: B,
+    pool_dest_token_balance: B,
+    amount_out: B,
+    fee: Permill,
+) -> Option<B> {
+    let output = amount_before_fee(amount_out, fee)?;
+    let new_dest_token_balance = to_u256(pool_dest_token_balance.checked_sub(&output)?)?;
+    let new_origin_token_balance: B = from_u256(div_ceil(constant, new_dest_token_balance)?)?;
+    Some(new_origin_token_balance.saturating_sub(pool_origin_token_balance))
+}
+
+// The reverse of taking the `fee`, returns the output before the fee (rounded up)
+pub fn amount_before_fee<B: AtLeast32BitUnsigned>(output: B, fee: Permill) -> Option<B> {
+    mul_div_ceil(
+        output,
+        B::from(Permill::ACCURACY),
+        B::from(fee.left_from_one().deconstruct()),
+    )
+}
+
+// The amount of the other asset that has to be deposited together with `amount`, rounded up
+pub fn deposit_corresponding_amount<B: AtLeast32BitUnsigned>(
+    amount: B,
+    pool_origin_token_balance: B,
+    pool_dest_token_balance: B,
+) -> Option<B> {
+    mul_div_ceil(amount, pool_dest_token_balance, pool_origin_token_balance)
+}
+
+// The pool share minted for depositing `amount`, rounded down
+pub fn shares_to_mint<B: AtLeast32BitUnsigned>(
+    amount: B,
+    pool_origin_token_balance: B,
+    total_shares: B,
+) -> Option<B> {
+    mul_div_floor(amount, total_shares, pool_origin_token_balance)
+}
+
+// The amount of the other asset that is withdrawn together with `amount`, rounded down
+pub fn withdraw_corresponding_amount<B: AtLeast32BitUnsigned>(
+    amount: B,
+    pool_origin_token_balance: B,
+    pool_dest_token_balance: B,
+) -> Option<B> {
+    mul_div_floor(amount, pool_dest_token_balance, pool_origin_token_balance)
+}
+
+// The pool share burned for withdrawing `amount`, rounded up
+pub fn shares_to_burn<B: AtLeast32BitUnsigned>(
+    amount: B,
+    pool_origin_token_balance: B,
+    total_shares: B,
+) -> Option<B> {
+    mul_div_ceil(amount, total_shares, pool_origin_token_balance)
+}
+
+// The amount of an asset that `shares` are worth, rounded down
+pub fn share_value<B: AtLeast32BitUnsigned>(
+    shares: B,
+    pool_token_balance: B,
+    total_shares: B,
+) -> Option<B> {
+    mul_div_floor(shares, pool_token_balance, total_shares)
+}
+
+// The amount `s` of the deposited asset to swap before `deposit_one_asset`, so that the
+// rest `a - s` matches the pool ratio after the swap. The fee `f` is taken from the output
+// `s * y * (1 - f) / (x + s)`, and solving for `s` gives
+// s^2 + s * (x * (2 - f) - a * f) - a * x = 0
+// All terms are multiplied by 100% to stay in integers, which needs 512 bits
+pub fn deposit_one_asset_swap_amount<B: AtLeast32BitUnsigned>(
+    fee: Permill,
+    pool_origin_token_balance: B,
+    amount: B,
+) -> Option<B> {
+    let hundred_percent = U512::from(Permill::ACCURACY);
+    let fee = U512::from(fee.deconstruct());
+    let x = U512::from(to_u256(pool_origin_token_balance)?);
+    let a = U512::from(to_u256(amount)?);
+
+    // b = x * (2 - f) - a * f, it can be negative for large deposits
+    let positive_part = x * (hundred_percent * 2 - fee);
+    let negative_part = a * fee;
+    let b_is_negative = negative_part > positive_part;
+    let b = if b_is_negative {
+        negative_part - positive_part
+    } else {
+        positive_part - negative_part
+    };
+
+    // sqrt(b^2 + 4 * a * x)
+    let sqrt = (b * b + a * x * 4 * hundred_percent * hundred_percent).integer_sqrt();
+    let numerator = if b_is_negative { sqrt + b } else { sqrt - b };
+    let swap_amount = numerator / (hundred_percent * 2);
+    from_u256(U256::try_from(swap_amount).ok()?)
+}
+
+// The amount `p` of the asset to withdraw before `withdraw_one_asset`, x - sqrt(x^2 - x * a)
+pub fn withdraw_one_asset_amount<B: AtLeast32BitUnsigned>(
+    pool_origin_token_balance: B,
+    amount: B,
+) -> Option<B> {
+    let x = to_u256(pool_origin_token_balance)?;
+    let rest = x
+        .checked_mul(x)?
+        .checked_sub(x.checked_mul(to_u256(amount)?)?)?;
+    pool_origin_token_balance.checked_sub(&from_u256(rest.integer_sqrt())?)
+}
+
+// The relative change of the spot price y / x from (x, y) to (x', y'),
+// |y' * x - y * x'| / (y * x')
+pub fn price_impact<B: AtLeast32BitUnsigned>(
+    origin_balance_before: B,
+    dest_balance_before: B,
+    origin_balance_after: B,
+    dest_balance_after: B,
+) -> Option<Permill> {
+    let price_after = mul(dest_balance_after, origin_balance_before)?;
+    let price_before = mul(dest_balance_before, origin_balance_after)?;
+    let price_change = if price_after > price_before {
+        price_after - price_before
+    } else {
+        price_before - price_after
+    };
+    Some(ratio(price_change, price_before))
+}
+
+// Whether the spot price y / x has dropped by at most `max_price_impact`,
+// compared without rounding, so an impact exactly at the bound is allowed
+pub fn is_within_price_impact<B: AtLeast32BitUnsigned>(
+    origin_balance_before: B,
+    dest_balance_before: B,
+    origin_balance_after: B,
+    dest_balance_after: B,
+    max_price_impact: Permill,
+) -> Option<bool> {
+    let price_after = mul(dest_balance_after, origin_balance_before)?;
+    let price_before = mul(dest_balance_before, origin_balance_after)?;
+    Some(cmp_part(price_before, max_price_impact.left_from_one(), price_after) != Ordering::Greater)
+}
@@ -0,0 +1,64 @@
+use crate::*;
+use proptest::prelude::*;
+
+#[test]
+fn swapping_rounds_in_favour_of_pool() {
+    let constant = U256::from(50 * 50);
+    // 2500 / 60 is rounded up to 42
+    assert_eq!(swap_output(constant, 50_u128, 50, 10), Some(8));
+    assert_eq!(
+        quote_out(constant, 50_u128, 50, 10, Permill::from_parts(3000)),
+        Some(7)
+    );
+    assert_eq!(
+        quote_in(constant, 50_u128, 50, 7, Permill::from_parts(3000)),
+        Some(10)
+    );
+}
+
+#[test]
+fn shares_round_in_favour_of_pool() {
+    assert_eq!(deposit_corresponding_amount(10_u128, 30, 20), Some(7));
+    assert_eq!(shares_to_mint(10_u128, 30, 1000), Some(333));
+    assert_eq!(withdraw_corresponding_amount(10_u128, 30, 20), Some(6));
+    assert_eq!(shares_to_burn(10_u128, 30, 1000), Some(334));
+    assert_eq!(share_value(333_u128, 30, 1000), Some(9));
+}
+
+#[test]
+fn price_impact_bounds() {
+    // The pool moves from 50/50 to 60/43
+    assert_eq!(
+        price_impact(50_u128, 50, 60, 43),
+        Some(Permill::from_parts(283_333))
+    );
+    assert_eq!(
+        is_within_price_impact(50_u128, 50, 60, 43, Permill::from_parts(283_333)),
+        Some(false)
+    );
+    assert_eq!(
+        is_within_price_impact(50_u128, 50, 60, 43, Permill::from_parts(283_334)),
+        Some(true)
+    );
+}
+
+proptest! {
+    #[test]
+    fn quote_in_is_smallest_input(
+        origin_balance in 1_u128..1_000_000_000_000,
+        dest_balance in 2_u128..1_000_000_000_000,
+        amount_out in 1_u128..1_000_000,
+        fee in 0_u32..100_000,
+    ) {
+        let constant = U256::from(origin_balance) * U256::from(dest_balance);
+        let fee = Permill::from_parts(fee);
+        if let Some(amount_in) = quote_in(constant, origin_balance, dest_balance, amount_out, fee) {
+            let output = quote_out(constant, origin_balance, dest_balance, amount_in, fee);
+            prop_assert!(output >= Some(amount_out));
+            if amount_in > 0 {
+                let output = quote_out(constant, origin_balance, dest_balance, amount_in - 1, fee);
+                prop_assert!(output < Some(amount_out));
+            }
+        }
+    }
+}
@@ -0,0 +1,11 @@
+//! Drives random sequences of pool operations against the mock runtime of the pallet
+//! and panics if any pool invariant breaks, see `pallet_dex::fuzzing`.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use pallet_dex::fuzzing::{self, Operation};
+
+fuzz_target!(|operations: Vec<Operation>| {
+    fuzzing::run(&operations);
+});
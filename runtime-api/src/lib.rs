@@ -0,0 +1,39 @@
+//! Runtime API of the DEX pallet, used by clients to quote swaps before sending them.
+
+#![cfg_attr(not(feature = "std"), no_std)]
+
+use codec::Codec;
+use sp_std::vec::Vec;
+
+sp_api::decl_runtime_apis! {
+    pub trait DexApi<AccountId, AssetId, Balance>
+    where
+        AccountId: Codec,
+        AssetId: Codec,
+        Balance: Codec,
+    {
+        // Returns the pools of the best route from `asset_in` to `asset_out` and its output
+        // The runtime is expected to call `Pallet::find_best_route`
+        fn quote_exact_in_auto(
+            asset_in: AssetId,
+            asset_out: AssetId,
+            amount: Balance,
+        ) -> Option<(Vec<AccountId>, Balance)>;
+
+        // Returns the pool of the pair with the most of `asset_out` for `amount` and its output
+        // The runtime is expected to call `Pallet::best_pool_for_pair`
+        fn best_pool_for_pair(
+            asset_in: AssetId,
+            asset_out: AssetId,
+            amount: Balance,
+        ) -> Option<(AccountId, Balance)>;
+
+        // Returns the amount of `asset_out` paid out by `remove_liquidity_one_asset` for `shares`
+        // The runtime is expected to call `Pallet::quote_remove_liquidity_one_asset`
+        fn quote_remove_liquidity_one_asset(
+            pool: AccountId,
+            shares: Balance,
+            asset_out: AssetId,
+        ) -> Option<Balance>;
+    }
+}
@@ -0,0 +1,213 @@
+// Adapters of asset pallets to the `fungibles` traits that `Config::Assets` expects
+
+use crate::{Config, NativeOrAsset};
+use frame_support::sp_std::marker::PhantomData;
+use frame_support::traits::{
+    tokens::{
+        fungible,
+        fungibles::{Inspect, Transfer},
+        DepositConsequence, WithdrawConsequence,
+    },
+    Currency, ExistenceRequirement,
+};
+use pallet_multi_token::multi_token::MultiTokenTrait;
+use sp_runtime::{
+    traits::{CheckedAdd, Zero},
+    DispatchError,
+};
+
+// Exposes `pallet_multi_token` (`MultiToken`) as `fungibles`
+// Multi tokens have no existential deposit, so accounts are never reaped and `keep_alive`
+// is ignored. The total issuance is not tracked by `MultiTokenTrait`, the pallet does not use it
+pub struct MultiTokenAdapter<T, MultiToken>(PhantomData<(T, MultiToken)>);
+
+impl<T, MultiToken> Inspect<T::AccountId> for MultiTokenAdapter<T, MultiToken>
+where
+    T: Config,
+    MultiToken: MultiTokenTrait<T, T::AssetId, T::Balance>,
+{
+    type AssetId = T::AssetId;
+    type Balance = T::Balance;
+
+    fn total_issuance(_asset: Self::AssetId) -> Self::Balance {
+        Zero::zero()
+    }
+
+    fn minimum_balance(_asset: Self::AssetId) -> Self::Balance {
+        Zero::zero()
+    }
+
+    fn balance(asset: Self::AssetId, who: &T::AccountId) -> Self::Balance {
+        MultiToken::get_balance(&asset, who).unwrap_or_else(Zero::zero)
+    }
+
+    fn reducible_balance(
+        asset: Self::AssetId,
+        who: &T::AccountId,
+        _keep_alive: bool,
+    ) -> Self::Balance {
+        Self::balance(asset, who)
+    }
+
+    fn can_deposit(
+        asset: Self::AssetId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+        _mint: bool,
+    ) -> DepositConsequence {
+        match Self::balance(asset, who).checked_add(&amount) {
+            Some(_) => DepositConsequence::Success,
+            None => DepositConsequence::Overflow,
+        }
+    }
+
+    fn can_withdraw(
+        asset: Self::AssetId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> WithdrawConsequence<Self::Balance> {
+        if amount <= Self::balance(asset, who) {
+            WithdrawConsequence::Success
+        } else {
+            WithdrawConsequence::NoFunds
+        }
+    }
+}
+
+impl<T, MultiToken> Transfer<T::AccountId> for MultiTokenAdapter<T, MultiToken>
+where
+    T: Config,
+    MultiToken: MultiTokenTrait<T, T::AssetId, T::Balance>,
+{
+    fn transfer(
+        asset: Self::AssetId,
+        source: &T::AccountId,
+        dest: &T::AccountId,
+        amount: Self::Balance,
+        _keep_alive: bool,
+    ) -> Result<Self::Balance, DispatchError> {
+        MultiToken::safe_transfer(source.clone(), source.clone(), dest.clone(), asset, amount)?;
+        Ok(amount)
+    }
+}
+
+// Exposes `Config::Currency` as `NativeOrAsset::Native` next to the assets of `Assets`,
+// so pools can trade the native currency the same way as any other asset
+// The currency has to implement `fungible::Inspect` as well (`pallet_balances` does), which
+// knows about locked and frozen balance
+pub struct NativeOrAssetAdapter<T, Assets>(PhantomData<(T, Assets)>);
+
+impl<T, Assets> Inspect<T::AccountId> for NativeOrAssetAdapter<T, Assets>
+where
+    T: Config,
+    T::Currency: Currency<T::AccountId, Balance = T::Balance>
+        + fungible::Inspect<T::AccountId, Balance = T::Balance>,
+    Assets: Inspect<T::AccountId, Balance = T::Balance>,
+{
+    type AssetId = NativeOrAsset<Assets::AssetId>;
+    type Balance = T::Balance;
+
+    fn total_issuance(asset: Self::AssetId) -> Self::Balance {
+        match asset {
+            NativeOrAsset::Native => <T::Currency as Currency<T::AccountId>>::total_issuance(),
+            NativeOrAsset::Asset(asset) => Assets::total_issuance(asset),
+        }
+    }
+
+    fn minimum_balance(asset: Self::AssetId) -> Self::Balance {
+        match asset {
+            NativeOrAsset::Native => <T::Currency as Currency<T::AccountId>>::minimum_balance(),
+            NativeOrAsset::Asset(asset) => Assets::minimum_balance(asset),
+        }
+    }
+
+    // Reserved native balance can not be traded, so only the free balance is counted
+    fn balance(asset: Self::AssetId, who: &T::AccountId) -> Self::Balance {
+        match asset {
+            NativeOrAsset::Native => T::Currency::free_balance(who),
+            NativeOrAsset::Asset(asset) => Assets::balance(asset, who),
+        }
+    }
+
+    fn reducible_balance(
+        asset: Self::AssetId,
+        who: &T::AccountId,
+        keep_alive: bool,
+    ) -> Self::Balance {
+        match asset {
+            // Locked balance can not be transferred either, so it is left to the currency
+            NativeOrAsset::Native => {
+                <T::Currency as fungible::Inspect<T::AccountId>>::reducible_balance(who, keep_alive)
+            }
+            NativeOrAsset::Asset(asset) => Assets::reducible_balance(asset, who, keep_alive),
+        }
+    }
+
+    fn can_deposit(
+        asset: Self::AssetId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+        mint: bool,
+    ) -> DepositConsequence {
+        match asset {
+            NativeOrAsset::Native => match T::Currency::free_balance(who).checked_add(&amount) {
+                None => DepositConsequence::Overflow,
+                Some(balance)
+                    if balance < <T::Currency as Currency<T::AccountId>>::minimum_balance() =>
+                {
+                    DepositConsequence::BelowMinimum
+                }
+                Some(_) => DepositConsequence::Success,
+            },
+            NativeOrAsset::Asset(asset) => Assets::can_deposit(asset, who, amount, mint),
+        }
+    }
+
+    fn can_withdraw(
+        asset: Self::AssetId,
+        who: &T::AccountId,
+        amount: Self::Balance,
+    ) -> WithdrawConsequence<Self::Balance> {
+        match asset {
+            NativeOrAsset::Native => {
+                if amount <= T::Currency::free_balance(who) {
+                    WithdrawConsequence::Success
+                } else {
+                    WithdrawConsequence::NoFunds
+                }
+            }
+            NativeOrAsset::Asset(asset) => Assets::can_withdraw(asset, who, amount),
+        }
+    }
+}
+
+impl<T, Assets> Transfer<T::AccountId> for NativeOrAssetAdapter<T, Assets>
+where
+    T: Config,
+    T::Currency: Currency<T::AccountId, Balance = T::Balance>
+        + fungible::Inspect<T::AccountId, Balance = T::Balance>,
+    Assets: Transfer<T::AccountId, Balance = T::Balance>,
+{
+    fn transfer(
+        asset: Self::AssetId,
+        source: &T::AccountId,
+        dest: &T::AccountId,
+        amount: Self::Balance,
+        keep_alive: bool,
+    ) -> Result<Self::Balance, DispatchError> {
+        match asset {
+            NativeOrAsset::Native => {
+                let existence_requirement = if keep_alive {
+                    ExistenceRequirement::KeepAlive
+                } else {
+                    ExistenceRequirement::AllowDeath
+                };
+                T::Currency::transfer(source, dest, amount, existence_requirement)?;
+                Ok(amount)
+            }
+            NativeOrAsset::Asset(asset) => {
+                Assets::transfer(asset, source, dest, amount, keep_alive)
+            }
+        }
+    }
+}
@@ -0,0 +1,268 @@
+// Random sequences of pool operations against the mock runtime, shared by the property tests
+// and the fuzz target. Every operation is allowed to fail, the invariants are checked after
+// each one and a violation panics
+
+use crate::{mock::*, Pools, TotalPoolShares};
+use frame_support::{assert_ok, dispatch::DispatchResult};
+use pallet_multi_token::multi_token::MultiTokenTrait;
+use sp_core::U256;
+
+pub const USERS: [u64; 2] = [1, 2];
+pub const POOLS: [u64; 2] = [101, 102];
+pub const ASSETS: [u64; 3] = [0, 1, 2];
+// The minted amount of every asset, split evenly between the users
+pub const SUPPLY: u128 = 1_000_000_000_000_000_000;
+
+// Users, pools and assets are indices into the constants above
+#[cfg_attr(feature = "fuzzing", derive(arbitrary::Arbitrary))]
+#[derive(Clone, Debug)]
+pub enum Operation {
+    Init {
+        user: u8,
+        pool: u8,
+        first_asset: u8,
+        first_amount: u128,
+        second_asset: u8,
+        second_amount: u128,
+    },
+    Swap {
+        user: u8,
+        pool: u8,
+        asset: u8,
+        amount: u128,
+    },
+    Deposit {
+        user: u8,
+        pool: u8,
+        asset: u8,
+        amount: u128,
+    },
+    Withdraw {
+        user: u8,
+        pool: u8,
+        asset: u8,
+        amount: u128,
+    },
+    DepositOneAsset {
+        user: u8,
+        pool: u8,
+        asset: u8,
+        amount: u128,
+    },
+    WithdrawOneAsset {
+        user: u8,
+        pool: u8,
+        asset: u8,
+        amount: u128,
+    },
+    RemoveLiquidityOneAsset {
+        user: u8,
+        pool: u8,
+        shares: u128,
+        asset: u8,
+    },
+}
+
+fn user(index: u8) -> u64 {
+    USERS[index as usize % USERS.len()]
+}
+
+fn pool(index: u8) -> u64 {
+    POOLS[index as usize % POOLS.len()]
+}
+
+fn asset(index: u8) -> u64 {
+    ASSETS[index as usize % ASSETS.len()]
+}
+
+impl Operation {
+    fn pool(&self) -> u64 {
+        match *self {
+            Operation::Init { pool: index, .. }
+            | Operation::Swap { pool: index, .. }
+            | Operation::Deposit { pool: index, .. }
+            | Operation::Withdraw { pool: index, .. }
+            | Operation::DepositOneAsset { pool: index, .. }
+            | Operation::WithdrawOneAsset { pool: index, .. }
+            | Operation::RemoveLiquidityOneAsset { pool: index, .. } => pool(index),
+        }
+    }
+
+    fn dispatch(&self) -> DispatchResult {
+        match *self {
+            Operation::Init {
+                user: u,
+                pool: p,
+                first_asset,
+                first_amount,
+                second_asset,
+                second_amount,
+            } => Dex::init(
+                Origin::signed(user(u)),
+                pool(p),
+                asset(first_asset),
+                first_amount,
+                asset(second_asset),
+                second_amount,
+                Dex::default_fee_tier(),
+            ),
+            Operation::Swap {
+                user: u,
+                pool: p,
+                asset: a,
+                amount,
+            } => Dex::swap_token(
+                Origin::signed(user(u)),
+                pool(p),
+                asset(a),
+                amount,
+                user(u),
+                None,
+            ),
+            Operation::Deposit {
+                user: u,
+                pool: p,
+                asset: a,
+                amount,
+            } => Dex::deposit(Origin::signed(user(u)), pool(p), asset(a), amount, None),
+            Operation::Withdraw {
+                user: u,
+                pool: p,
+                asset: a,
+                amount,
+            } => Dex::withdraw(
+                Origin::signed(user(u)),
+                pool(p),
+                asset(a),
+                amount,
+                user(u),
+                None,
+            ),
+            Operation::DepositOneAsset {
+                user: u,
+                pool: p,
+                asset: a,
+                amount,
+            } => Dex::deposit_one_asset(
+                Origin::signed(user(u)),
+                pool(p),
+                asset(a),
+                amount,
+                0,
+                user(u),
+                None,
+            ),
+            Operation::WithdrawOneAsset {
+                user: u,
+                pool: p,
+                asset: a,
+                amount,
+            } => Dex::withdraw_one_asset(
+                Origin::signed(user(u)),
+                pool(p),
+                asset(a),
+                amount,
+                user(u),
+                None,
+            ),
+            Operation::RemoveLiquidityOneAsset {
+                user: u,
+                pool: p,
+                shares,
+                asset: a,
+            } => Dex::remove_liquidity_one_asset(
+                Origin::signed(user(u)),
+                pool(p),
+                shares,
+                asset(a),
+                0,
+                user(u),
+                None,
+            ),
+        }
+    }
+}
+
+// The product of the pool balances and the total share of the pool
+fn pool_state(pool: u64) -> Option<(U256, u128)> {
+    let info = Dex::get_pool(pool)?;
+    let first_balance = MultiTokenPallet::get_balance(&info.first_asset, &pool).unwrap_or_default();
+    let second_balance =
+        MultiTokenPallet::get_balance(&info.second_asset, &pool).unwrap_or_default();
+    Some((
+        U256::from(first_balance) * U256::from(second_balance),
+        TotalPoolShares::<Test>::get(pool).unwrap_or_default(),
+    ))
+}
+
+fn setup() {
+    for asset in ASSETS {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(USERS[0])));
+        assert_ok!(MultiTokenPallet::mint(
+            Origin::signed(USERS[0]),
+            asset,
+            SUPPLY
+        ));
+        for user in &USERS[1..] {
+            assert_ok!(MultiTokenPallet::transfer(
+                Origin::signed(USERS[0]),
+                USERS[0],
+                *user,
+                asset,
+                SUPPLY / USERS.len() as u128
+            ));
+        }
+    }
+}
+
+// Invariants that hold after any operation, successful or not
+pub fn check_invariants() {
+    assert_ok!(Dex::do_try_state());
+    // Nobody transfers tokens to pools directly here, so the constant is exact
+    for (pool, info) in Pools::<Test>::iter() {
+        let (product, _) = pool_state(pool).unwrap();
+        assert_eq!(info.constant, product);
+    }
+
+    // Tokens only move between users and pools, so nothing is created or lost
+    for asset in ASSETS {
+        let total: u128 = USERS
+            .iter()
+            .chain(POOLS.iter())
+            .map(|account| MultiTokenPallet::get_balance(&asset, account).unwrap_or_default())
+            .sum();
+        assert_eq!(total, SUPPLY);
+    }
+}
+
+// Applies the operations one by one and checks the invariants after each of them
+pub fn run(operations: &[Operation]) {
+    new_test_ext().execute_with(|| {
+        setup();
+        for operation in operations {
+            let pool = operation.pool();
+            let before = pool_state(pool);
+            let result = operation.dispatch();
+            check_invariants();
+
+            let (before, after) = match (before, pool_state(pool)) {
+                (Some(before), Some(after)) => (before, after),
+                _ => continue,
+            };
+            let ((product_before, shares_before), (product_after, shares_after)) = (before, after);
+            // Swaps never decrease the product of the pool balances
+            if result.is_ok() && matches!(operation, Operation::Swap { .. }) {
+                assert!(product_after >= product_before);
+            }
+            // Nobody can dilute liquidity providers, so the product per squared share
+            // never decreases
+            if shares_after != 0 {
+                let shares_before = U256::from(shares_before) * U256::from(shares_before);
+                let shares_after = U256::from(shares_after) * U256::from(shares_after);
+                assert!(
+                    product_after.full_mul(shares_before) >= product_before.full_mul(shares_after)
+                );
+            }
+        }
+    });
+}
@@ -1,27 +1,180 @@
 #![cfg_attr(not(feature = "std"), no_std)]
 
+use codec::{Decode, Encode, MaxEncodedLen};
+use frame_support::traits::Currency;
+use scale_info::TypeInfo;
+#[cfg(feature = "std")]
+use serde::{Deserialize, Serialize};
+use sp_core::U256;
+use sp_runtime::traits::Saturating;
 use sp_runtime::traits::StaticLookup;
-#[cfg(test)]
+use sp_runtime::{Permill, RuntimeDebug};
+pub mod adapters;
+#[cfg(any(test, feature = "fuzzing"))]
+pub mod fuzzing;
+pub mod migrations;
+#[cfg(any(test, feature = "fuzzing"))]
 pub mod mock;
 #[cfg(test)]
+mod mock_assets;
+#[cfg(test)]
 mod test;
+pub mod traits;
+pub mod weights;
 
+// The pricing math lives in the `dex-math` crate, so clients can use the same functions
+pub use dex_math as math;
 pub use pallet::*;
+pub use weights::WeightInfo;
 
 type AccountIdLookupOf<T> = <<T as frame_system::Config>::Lookup as StaticLookup>::Source;
+type DepositBalanceOf<T> =
+    <<T as Config>::Currency as Currency<<T as frame_system::Config>::AccountId>>::Balance;
+type LiquidityOperationOf<T> = LiquidityOperation<
+    <T as frame_system::Config>::AccountId,
+    <T as Config>::AssetId,
+    <T as Config>::Balance,
+>;
+type PoolInfoOf<T> = PoolInfo<
+    <T as frame_system::Config>::AccountId,
+    <T as Config>::AssetId,
+    <T as Config>::Balance,
+    DepositBalanceOf<T>,
+>;
+
+// Info about a pool which is stored in `Pools`
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub struct PoolInfo<AccountId, AssetId, Balance, DepositBalance> {
+    // Pair of assets in the pool
+    pub first_asset: AssetId,
+    pub second_asset: AssetId,
+    // Pool constant, used in constant product formula
+    // It is a product of two balances, so it is stored in 256 bits
+    pub constant: U256,
+    // Account that created the pool and holds the creation deposit
+    pub creator: AccountId,
+    // Amount reserved from the creator on pool creation
+    pub deposit: DepositBalance,
+    // Whether the pool can be traded with
+    pub status: PoolStatus,
+    // A swap fee of the pool, there is one pool per pair of assets and fee tier
+    pub fee_tier: Permill,
+}
+
+// Balances of a pool at its first swap in a block, used by the circuit breaker
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub struct BlockStartPrice<BlockNumber, Balance> {
+    pub block: BlockNumber,
+    pub first_asset_balance: Balance,
+    pub second_asset_balance: Balance,
+}
+
+// Bounds and state of a pool fee that follows the volatility of the pool price
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub struct DynamicFee {
+    pub min_fee: Permill,
+    pub max_fee: Permill,
+    // Exponential moving average of relative price changes made by swaps
+    pub volatility: Permill,
+}
+
+impl DynamicFee {
+    // The fee grows one to one with the volatility, staying between the bounds
+    pub fn current_fee(&self) -> Permill {
+        self.min_fee
+            .saturating_add(self.volatility)
+            .min(self.max_fee)
+    }
+}
+
+// A single operation of `batch_liquidity`, the result goes to the caller
+#[derive(Clone, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub enum LiquidityOperation<AccountId, AssetId, Balance> {
+    // Same as `deposit`
+    Add {
+        pool: AccountId,
+        token_id: AssetId,
+        amount: Balance,
+    },
+    // Same as `withdraw`
+    Remove {
+        pool: AccountId,
+        token_id: AssetId,
+        amount: Balance,
+    },
+    // Same as `swap_token`
+    Swap {
+        pool: AccountId,
+        token_id: AssetId,
+        amount: Balance,
+    },
+}
+
+// An asset that is either the native currency (`Config::Currency`) or an asset of another pallet
+// Used as `Config::AssetId` together with `adapters::NativeOrAssetAdapter`
+#[cfg_attr(feature = "std", derive(Serialize, Deserialize))]
+#[derive(
+    Clone,
+    Copy,
+    Encode,
+    Decode,
+    Eq,
+    PartialEq,
+    Ord,
+    PartialOrd,
+    RuntimeDebug,
+    MaxEncodedLen,
+    TypeInfo,
+)]
+pub enum NativeOrAsset<AssetId> {
+    Native,
+    Asset(AssetId),
+}
+
+impl<AssetId> Default for NativeOrAsset<AssetId> {
+    fn default() -> Self {
+        NativeOrAsset::Native
+    }
+}
+
+impl<AssetId> From<AssetId> for NativeOrAsset<AssetId> {
+    fn from(asset: AssetId) -> Self {
+        NativeOrAsset::Asset(asset)
+    }
+}
+
+#[derive(Clone, Copy, Encode, Decode, Eq, PartialEq, RuntimeDebug, MaxEncodedLen, TypeInfo)]
+pub enum PoolStatus {
+    // The pool is open for swaps, deposits and withdrawals
+    Live,
+    // The pool is frozen by the admin, only withdrawals are allowed
+    Frozen,
+    // The pool storage is being cleared by `destroy_pool`
+    Destroying,
+}
 
 #[frame_support::pallet]
 pub mod pallet {
     use super::*;
-    use frame_support::dispatch::HasCompact;
+    use crate::traits::{FlashLoan, OnFlashLoan, OnFlashSwap};
+    use crate::{LiquidityOperation, LiquidityOperationOf, WeightInfo};
+    use frame_support::sp_std::cmp::Ordering;
+    use frame_support::sp_std::prelude::*;
+    use frame_support::traits::tokens::fungibles::{Inspect, Transfer};
+    use frame_support::traits::tokens::DepositConsequence;
+    use frame_support::traits::ReservableCurrency;
     use frame_support::{pallet_prelude::*, Blake2_128Concat};
     use frame_system::pallet_prelude::*;
-    use pallet_multi_token::multi_token::MultiTokenTrait;
-    use sp_runtime::traits::{AtLeast32BitUnsigned, Zero};
-    use sp_runtime::traits::{CheckedAdd, CheckedDiv, CheckedMul, CheckedSub, IntegerSquareRoot};
+    use sp_runtime::traits::{AtLeast32BitUnsigned, CheckedAdd, CheckedSub, Zero};
+    use sp_runtime::PerThing;
+
+    // Version 1 converts `Pools` values into `PoolInfo` and registers the pallet as a provider
+    // of every pool account
+    const STORAGE_VERSION: StorageVersion = StorageVersion::new(1);
 
     #[pallet::pallet]
     #[pallet::generate_store(pub(super) trait Store)]
+    #[pallet::storage_version(STORAGE_VERSION)]
     pub struct Pallet<T>(_);
 
     #[pallet::config]
@@ -29,7 +182,7 @@ pub mod pallet {
         type Event: From<Event<Self>> + IsType<<Self as frame_system::Config>::Event>;
 
         // A number-like type which is used to store balances, shares of the pool and fees
-        // Assumed to be the same one as the pallet_multi_token uses
+        // The same one as `Config::Assets` uses
         type Balance: Member
             + Parameter
             + AtLeast32BitUnsigned
@@ -39,18 +192,16 @@ pub mod pallet {
             + MaxEncodedLen
             + TypeInfo;
 
-        // A number-like type which is used to store id of an asset
-        // Assumed to be the same one as the pallet_multi_token uses
+        // A type which is used to store id of an asset, for example a number or `NativeOrAsset`
+        // The same one as `Config::Assets` uses
         type AssetId: Member
             + Parameter
-            + AtLeast32BitUnsigned
+            + Ord
             + Default
             + Copy
-            + HasCompact
             + MaybeSerializeDeserialize
             + MaxEncodedLen
-            + TypeInfo
-            + Zero;
+            + TypeInfo;
 
         // A share that is assigned to a pool creator
         #[pallet::constant]
@@ -61,10 +212,69 @@ pub mod pallet {
         type HundredPercent: Get<Self::Balance>;
 
         // A constant of hundred percent minus a fee mark
+        // The fee is the default fee tier, which is always allowed
         #[pallet::constant]
         type HundredPercentMinusFee: Get<Self::Balance>;
 
-        type MultiToken: MultiTokenTrait<Self, Self::AssetId, Self::Balance>;
+        // A weight of the latest price change in the volatility of pools with dynamic fees
+        #[pallet::constant]
+        type VolatilitySmoothing: Get<Permill>;
+
+        // Assets that are traded in pools, for example `pallet_assets`, `orml_tokens` or
+        // `pallet_multi_token` through `adapters::MultiTokenAdapter`
+        type Assets: Inspect<Self::AccountId, AssetId = Self::AssetId, Balance = Self::Balance>
+            + Transfer<Self::AccountId>;
+
+        // A native currency that is used for the pool creation deposit
+        type Currency: ReservableCurrency<Self::AccountId>;
+
+        // An amount of native currency that is reserved from the pool creator
+        // It is returned to the creator when the pool is destroyed
+        #[pallet::constant]
+        type PoolCreationDeposit: Get<DepositBalanceOf<Self>>;
+
+        // An origin that is allowed to create pools, resolves to the creator's account
+        type CreatePoolOrigin: EnsureOrigin<Self::Origin, Success = Self::AccountId>;
+
+        // An origin that is allowed to manage the asset allow-list and freeze pools
+        type AdminOrigin: EnsureOrigin<Self::Origin>;
+
+        // An account that receives leftover tokens of destroyed pools
+        #[pallet::constant]
+        type DustReceiver: Get<Self::AccountId>;
+
+        // A maximum amount of `PoolShares` entries removed by one `destroy_pool` call
+        #[pallet::constant]
+        type DestroyBatchSize: Get<u32>;
+
+        // A maximum amount of pools in a route found by `swap_exact_in_auto`
+        #[pallet::constant]
+        type MaxHops: Get<u32>;
+
+        // A maximum amount of pools quoted while searching for a route, bounds the weight
+        #[pallet::constant]
+        type MaxRouteCandidates: Get<u32>;
+
+        // A maximum amount of routes in a single `swap_split`
+        #[pallet::constant]
+        type MaxSplitRoutes: Get<u32>;
+
+        // A maximum amount of operations in a single `batch_liquidity`
+        #[pallet::constant]
+        type MaxBatchOperations: Get<u32>;
+
+        // Weights of the extrinsics
+        type WeightInfo: WeightInfo;
+
+        // A callback that receives the output of a flash swap and repays the pool
+        type FlashSwapHandler: OnFlashSwap<Self::AccountId, Self::AssetId, Self::Balance>;
+
+        // A callback that receives flash loans and repays them
+        type FlashLoanReceiver: OnFlashLoan<Self::AccountId, Self::AssetId, Self::Balance>;
+
+        // A fee for flash loans relative to `HundredPercent`, it is kept in the pool
+        #[pallet::constant]
+        type FlashLoanFee: Get<Self::Balance>;
     }
 
     #[pallet::storage]
@@ -72,8 +282,8 @@ pub mod pallet {
     pub type Pools<T: Config> = StorageMap<
         _,
         Blake2_128Concat,
-        T::AccountId,                         // Pool address
-        (T::AssetId, T::AssetId, T::Balance), // Pair of assets in the pool & Pool constant
+        T::AccountId,  // Pool address
+        PoolInfoOf<T>, // Pair of assets in the pool, Pool constant & creation deposit
     >;
 
     #[pallet::storage]
@@ -91,6 +301,145 @@ pub mod pallet {
     #[pallet::getter(fn get_total_pool_shares)]
     pub type TotalPoolShares<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, T::Balance>;
 
+    #[pallet::storage]
+    #[pallet::getter(fn get_default_max_price_impact)]
+    pub type DefaultMaxPriceImpact<T: Config> = StorageValue<_, Permill>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_pool_max_price_impact)]
+    pub type PoolMaxPriceImpact<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, Permill>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_dynamic_fee)]
+    pub type DynamicFees<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, DynamicFee>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_circuit_breaker_threshold)]
+    pub type CircuitBreakerThreshold<T: Config> = StorageValue<_, Permill>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_block_start_price)]
+    pub type BlockStartPrices<T: Config> = StorageMap<
+        _,
+        Blake2_128Concat,
+        T::AccountId, // Pool address
+        BlockStartPrice<T::BlockNumber, T::Balance>,
+    >;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_pool_by_pair)]
+    pub type PoolIndex<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        (T::AssetId, T::AssetId), // Pair of assets in ascending order
+        Blake2_128Concat,
+        Permill,      // Fee tier
+        T::AccountId, // Pool address
+    >;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_asset_pool)]
+    pub type AssetPools<T: Config> = StorageDoubleMap<
+        _,
+        Blake2_128Concat,
+        T::AssetId, // Asset
+        Blake2_128Concat,
+        T::AccountId, // Address of a pool that contains the asset
+        (),
+    >;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_fee_tier)]
+    pub type FeeTiers<T: Config> = StorageMap<_, Blake2_128Concat, Permill, ()>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn is_allow_list_enabled)]
+    pub type AllowListEnabled<T: Config> = StorageValue<_, bool, ValueQuery>;
+
+    #[pallet::storage]
+    #[pallet::getter(fn get_allowed_asset)]
+    pub type AllowedAssets<T: Config> = StorageMap<_, Blake2_128Concat, T::AssetId, ()>;
+
+    // Pools whose reserves are held by a flash swap handler or a flash loan receiver,
+    // nothing can be done with them until the callback returns
+    #[pallet::storage]
+    pub type LockedPools<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
+
+    // Accounts that allow transfers to the pools to drop them below the minimum balance
+    #[pallet::storage]
+    pub type AllowDeath<T: Config> = StorageMap<_, Blake2_128Concat, T::AccountId, ()>;
+
+    // A pool created at genesis: creator, pool account, first asset, first asset amount,
+    // second asset, second asset amount and fee tier
+    pub type GenesisPoolOf<T> = (
+        <T as frame_system::Config>::AccountId,
+        <T as frame_system::Config>::AccountId,
+        <T as Config>::AssetId,
+        <T as Config>::Balance,
+        <T as Config>::AssetId,
+        <T as Config>::Balance,
+        Permill,
+    );
+
+    #[pallet::genesis_config]
+    pub struct GenesisConfig<T: Config> {
+        // Fee tiers that are allowed in addition to the default one
+        pub fee_tiers: Vec<Permill>,
+        // Pools are created the same way as with `init`, so the creators must hold the assets
+        // and the pool creation deposit, which are transferred from them
+        pub pools: Vec<GenesisPoolOf<T>>,
+    }
+
+    #[cfg(feature = "std")]
+    impl<T: Config> Default for GenesisConfig<T> {
+        fn default() -> Self {
+            Self {
+                fee_tiers: Default::default(),
+                pools: Default::default(),
+            }
+        }
+    }
+
+    #[pallet::genesis_build]
+    impl<T: Config> GenesisBuild<T> for GenesisConfig<T> {
+        fn build(&self) {
+            for fee_tier in &self.fee_tiers {
+                assert!(
+                    !Pallet::<T>::is_fee_tier_allowed(fee_tier),
+                    "Fee tier {:?} is already allowed",
+                    fee_tier
+                );
+                FeeTiers::<T>::insert(fee_tier, ());
+            }
+
+            for (
+                index,
+                (
+                    creator,
+                    pool,
+                    first_token_id,
+                    first_token_amount,
+                    second_token_id,
+                    second_token_amount,
+                    fee_tier,
+                ),
+            ) in self.pools.iter().enumerate()
+            {
+                if let Err(error) = Pallet::<T>::do_init(
+                    creator.clone(),
+                    pool.clone(),
+                    *first_token_id,
+                    *first_token_amount,
+                    *second_token_id,
+                    *second_token_amount,
+                    *fee_tier,
+                ) {
+                    panic!("Genesis pool {} can not be created: {:?}", index, error);
+                }
+            }
+        }
+    }
+
     #[pallet::event]
     #[pallet::generate_deposit(pub fn deposit_event)]
     pub enum Event<T: Config> {
@@ -99,17 +448,21 @@ pub mod pallet {
             pool_account: T::AccountId,
             first_asset: T::AssetId,
             second_asset: T::AssetId,
+            fee_tier: Permill,
         },
         Swapped {
             operator: T::AccountId,
+            recipient: T::AccountId,
             pool_account: T::AccountId,
             first_asset: T::AssetId,
             first_asset_amount: T::Balance,
             second_asset: T::AssetId,
             second_asset_amount: T::Balance,
+            fee: Permill,
         },
         Deposited {
             operator: T::AccountId,
+            recipient: T::AccountId,
             pool_account: T::AccountId,
             first_asset: T::AssetId,
             first_asset_amount: T::Balance,
@@ -118,12 +471,122 @@ pub mod pallet {
         },
         Withdrawed {
             operator: T::AccountId,
+            recipient: T::AccountId,
             pool_account: T::AccountId,
             first_asset: T::AssetId,
             first_asset_amount: T::Balance,
             second_asset: T::AssetId,
             second_asset_amount: T::Balance,
         },
+        FeeTierAdded {
+            fee_tier: Permill,
+        },
+        FeeTierRemoved {
+            fee_tier: Permill,
+        },
+        AssetListed {
+            asset: T::AssetId,
+        },
+        AssetDelisted {
+            asset: T::AssetId,
+        },
+        AllowListToggled {
+            enabled: bool,
+        },
+        AllowDeathSet {
+            account: T::AccountId,
+            allow_death: bool,
+        },
+        PoolFrozen {
+            pool_account: T::AccountId,
+        },
+        PoolThawed {
+            pool_account: T::AccountId,
+        },
+        PoolDestroyed {
+            pool_account: T::AccountId,
+            first_asset: T::AssetId,
+            first_asset_dust: T::Balance,
+            second_asset: T::AssetId,
+            second_asset_dust: T::Balance,
+        },
+        FlashSwapped {
+            operator: T::AccountId,
+            pool_account: T::AccountId,
+            asset_out: T::AssetId,
+            amount_out: T::Balance,
+            asset_in: T::AssetId,
+            amount_in: T::Balance,
+        },
+        MaxPriceImpactSet {
+            pool_account: Option<T::AccountId>,
+            max_price_impact: Option<Permill>,
+        },
+        DynamicFeeSet {
+            pool_account: T::AccountId,
+            fee_bounds: Option<(Permill, Permill)>,
+        },
+        CircuitBreakerThresholdSet {
+            threshold: Option<Permill>,
+        },
+        RouteSwapped {
+            operator: T::AccountId,
+            recipient: T::AccountId,
+            route: Vec<T::AccountId>,
+            asset_in: T::AssetId,
+            amount_in: T::Balance,
+            asset_out: T::AssetId,
+            amount_out: T::Balance,
+        },
+        SplitSwapped {
+            operator: T::AccountId,
+            recipient: T::AccountId,
+            routes: u32,
+            asset_in: T::AssetId,
+            amount_in: T::Balance,
+            asset_out: T::AssetId,
+            amount_out: T::Balance,
+        },
+        // Sent after `Deposited` of `deposit_one_asset`, `refund` of `refund_asset` is left
+        // with the operator because of rounding
+        DepositedOneAsset {
+            operator: T::AccountId,
+            recipient: T::AccountId,
+            pool_account: T::AccountId,
+            asset: T::AssetId,
+            amount: T::Balance,
+            shares: T::Balance,
+            refund_asset: T::AssetId,
+            refund: T::Balance,
+        },
+        // Sent after `Swapped` and `Withdrawed` of `remove_liquidity_one_asset`
+        // `amount` does not include the other asset paid out to the last liquidity provider
+        WithdrawedOneAsset {
+            operator: T::AccountId,
+            recipient: T::AccountId,
+            pool_account: T::AccountId,
+            shares: T::Balance,
+            asset: T::AssetId,
+            amount: T::Balance,
+        },
+        // An operation of `batch_liquidity` has been executed
+        // `amount` is the other asset of the pool paid for `Add`, withdrawn for `Remove`
+        // and received for `Swap`
+        BatchOperationCompleted {
+            index: u32,
+            amount: T::Balance,
+        },
+        BatchCompleted {
+            operator: T::AccountId,
+            operations: u32,
+        },
+        FlashLoaned {
+            borrower: T::AccountId,
+            pool_account: T::AccountId,
+            asset: T::AssetId,
+            borrowed: T::Balance,
+            repaid: T::Balance,
+        },
     }
 
     #[pallet::error]
@@ -146,11 +609,97 @@ pub mod pallet {
         EmptyPool,
         // Initialization of the pool with both assets being same
         SameAssetPool,
+        // Initialization of the pool with an asset that is not on the allow-list
+        AssetNotAllowed,
+        // Trying to list an asset that is already on the allow-list
+        AssetAlreadyListed,
+        // Trying to delist an asset that is not on the allow-list
+        AssetNotListed,
+        // There is not enough native currency to reserve the pool creation deposit
+        NotEnoughBalanceForDeposit,
+        // The pool is frozen or being destroyed
+        PoolNotLive,
+        // Trying to thaw a pool that is not frozen
+        PoolNotFrozen,
+        // Trying to destroy a pool that still has liquidity providers
+        PoolNotDestroyable,
+        // The pool constant does not hold after the flash swap handler has returned
+        FlashSwapNotRepaid,
+        // The pool has not received the loan and the fee after the flash loan receiver has returned
+        FlashLoanNotRepaid,
+        // The extrinsic has been executed after its deadline block
+        DeadlinePassed,
+        // The swap moves the spot price of the pool by more than the allowed bound
+        PriceImpactTooHigh,
+        // The swap would move the price of the pool by more than `CircuitBreakerThreshold`
+        // since the start of the block
+        CircuitBreakerActive,
+        // The minimum fee is greater than the maximum fee
+        InvalidFeeBounds,
+        // Initialization of the pool with a fee tier that is not allowed
+        FeeTierNotAllowed,
+        // Trying to add a fee tier that is already allowed
+        FeeTierAlreadyExists,
+        // Trying to remove a fee tier that is not in `FeeTiers`
+        NoSuchFeeTier,
+        // Trying to burn more pool share than the account has
+        NotEnoughShares,
+        // There is no route between the assets within `Config::MaxHops` pools
+        NoRouteFound,
+        // The output of the swap or the received pool share is lower than the requested minimum
+        SlippageExceeded,
+        // The route is empty or does not lead from the input asset to the output asset
+        InvalidRoute,
+        // The pool is used by a flash swap or a flash loan, it can not be used until it is repaid
+        PoolLocked,
+        // Trying to remove the fee of `Config`, the default fee tier is always allowed
+        DefaultFeeTierNotRemovable,
+    }
+
+    #[pallet::hooks]
+    impl<T: Config> Hooks<BlockNumberFor<T>> for Pallet<T> {
+        fn integrity_test() {
+            assert!(
+                !T::HundredPercent::get().is_zero(),
+                "`HundredPercent` must be greater than zero"
+            );
+            assert!(
+                T::HundredPercentMinusFee::get() <= T::HundredPercent::get(),
+                "`HundredPercentMinusFee` must not exceed `HundredPercent`"
+            );
+            assert!(
+                !T::DefaultShare::get().is_zero(),
+                "`DefaultShare` must be greater than zero"
+            );
+            assert!(T::MaxHops::get() > 0, "`MaxHops` must be greater than zero");
+        }
+
+        fn on_runtime_upgrade() -> Weight {
+            if Pallet::<T>::on_chain_storage_version() >= 1 {
+                return T::DbWeight::get().reads(1);
+            }
+
+            // Values of the old layout can not be decoded, so everything that needs the pools
+            // is done by the migration while it translates them
+            let weight = crate::migrations::v1::migrate::<T>();
+            STORAGE_VERSION.put::<Pallet<T>>();
+
+            weight.saturating_add(T::DbWeight::get().reads_writes(1, 1))
+        }
+
+        #[cfg(feature = "try-runtime")]
+        fn post_upgrade() -> Result<(), &'static str> {
+            ensure!(
+                Pallet::<T>::on_chain_storage_version() == STORAGE_VERSION,
+                "Storage version of the pallet has not been updated"
+            );
+            Self::do_try_state()
+        }
     }
 
     #[pallet::call]
     impl<T: Config> Pallet<T> {
-        #[pallet::weight(1000)]
+        #[pallet::weight(T::WeightInfo::init())]
         pub fn init(
             origin: OriginFor<T>,
             pool_address: AccountIdLookupOf<T>,
@@ -158,103 +707,119 @@ pub mod pallet {
             first_token_amount: T::Balance,
             second_token_id: T::AssetId,
             second_token_amount: T::Balance,
+            fee_tier: Permill,
         ) -> DispatchResult {
-            let creator = ensure_signed(origin)?;
+            let creator = T::CreatePoolOrigin::ensure_origin(origin)?;
             let pool = T::Lookup::lookup(pool_address)?;
-
-            ensure!(
-                !first_token_amount.is_zero() && !second_token_amount.is_zero(),
-                Error::<T>::DepositingZeroAmount
-            );
-            ensure!(Self::get_pool(&pool) == None, Error::<T>::PoolAlreadyExists);
-            ensure!(first_token_id != second_token_id, Error::<T>::SameAssetPool);
-            Self::check_balance(&first_token_id, &creator, first_token_amount)?;
-            Self::check_balance(&second_token_id, &creator, second_token_amount)?;
-
-            let pool_constant = first_token_amount
-                .checked_mul(&second_token_amount)
-                .ok_or(Error::<T>::Overflow)?;
-
-            T::MultiToken::safe_transfer(
-                creator.clone(),
-                creator.clone(),
-                pool.clone(),
+            Self::do_init(
+                creator,
+                pool,
                 first_token_id,
                 first_token_amount,
-            )?;
-
-            T::MultiToken::safe_transfer(
-                creator.clone(),
-                creator.clone(),
-                pool.clone(),
                 second_token_id,
                 second_token_amount,
-            )?;
-
-            Pools::<T>::insert(&pool, (first_token_id, second_token_id, pool_constant));
-            PoolShares::<T>::insert(&pool, &creator, T::DefaultShare::get());
-            TotalPoolShares::<T>::insert(&pool, T::DefaultShare::get());
-
-            Ok(())
+                fee_tier,
+            )
         }
 
-        #[pallet::weight(1000)]
+        #[pallet::weight(T::WeightInfo::swap_token())]
         pub fn swap_token(
             origin: OriginFor<T>,
             pool_address: AccountIdLookupOf<T>,
             token_id: T::AssetId,
             amount: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
         ) -> DispatchResult {
             let operator = ensure_signed(origin)?;
             let pool = T::Lookup::lookup(pool_address)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
 
-            Self::swap(operator, pool, token_id, amount)?;
+            let keep_alive = !Self::is_death_allowed(&operator);
+            Self::swap(operator, recipient, pool, token_id, amount, keep_alive)?;
             Ok(())
         }
 
-        #[pallet::weight(1000)]
+        #[pallet::weight(T::WeightInfo::deposit())]
         pub fn deposit(
             origin: OriginFor<T>,
             pool_address: AccountIdLookupOf<T>,
             token_id: T::AssetId,
             amount: T::Balance,
+            deadline: Option<BlockNumberFor<T>>,
         ) -> DispatchResult {
             let operator = ensure_signed(origin)?;
             let pool = T::Lookup::lookup(pool_address)?;
+            Self::ensure_deadline(deadline)?;
 
-            Self::dep(operator, pool, token_id, amount)
+            let keep_alive = !Self::is_death_allowed(&operator);
+            Self::dep(
+                operator.clone(),
+                operator,
+                pool,
+                token_id,
+                amount,
+                keep_alive,
+            )?;
+            Ok(())
         }
 
-        #[pallet::weight(1000)]
+        #[pallet::weight(T::WeightInfo::withdraw())]
         pub fn withdraw(
             origin: OriginFor<T>,
             pool_address: AccountIdLookupOf<T>,
             token_id: T::AssetId,
             amount: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
         ) -> DispatchResult {
             let operator = ensure_signed(origin)?;
             let pool = T::Lookup::lookup(pool_address)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
 
-            Self::with(operator, pool, token_id, amount)?;
+            Self::with(operator, recipient, pool, token_id, amount)?;
             Ok(())
         }
 
-        #[pallet::weight(1000)]
+        // Swaps a part of `amount` so that the rest matches the pool ratio and deposits both
+        // Rounding leftovers of either asset stay with the operator and are recorded in the event
+        #[pallet::weight(T::WeightInfo::deposit_one_asset())]
         pub fn deposit_one_asset(
             origin: OriginFor<T>,
             pool_address: AccountIdLookupOf<T>,
             token_id: T::AssetId,
             amount: T::Balance,
+            min_shares_out: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
         ) -> DispatchResult {
             let operator = ensure_signed(origin)?;
             let pool = T::Lookup::lookup(pool_address)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
 
             ensure!(!amount.is_zero(), Error::<T>::DepositingZeroAmount);
             ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
-            Self::check_balance(&token_id, &operator, amount)?;
+            Self::ensure_pool_unlocked(&pool)?;
+            // The whole amount is checked here with the account of the operator kept alive,
+            // the swapped part is received back within the call, so the following transfers
+            // do not have to keep the account alive again
+            Self::check_balance(
+                &token_id,
+                &operator,
+                amount,
+                !Self::is_death_allowed(&operator),
+            )?;
 
             // We have already checked that pool exists, unwrap is safe
-            let (first_asset_id, second_asset_id, _) = Self::get_pool(&pool).unwrap();
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                fee_tier,
+                ..
+            } = Self::get_pool(&pool).unwrap();
             let corresponding_token_id = if token_id == first_asset_id {
                 second_asset_id
             } else if token_id == second_asset_id {
@@ -265,12 +830,9 @@ pub mod pallet {
             };
 
             // x
-            let pool_origin_token_balance =
-                T::MultiToken::get_balance(&token_id, &pool).ok_or(Error::<T>::EmptyPool)?;
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
             // y
-            let pool_dest_token_balance =
-                T::MultiToken::get_balance(&corresponding_token_id, &pool)
-                    .ok_or(Error::<T>::EmptyPool)?;
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
 
             ensure!(
                 !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
@@ -278,43 +840,112 @@ pub mod pallet {
             );
 
             // Calculation of the amount that needs to be swapped
-            let partial_result = pool_origin_token_balance
-                .checked_mul(&pool_origin_token_balance)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_add(
-                    &pool_origin_token_balance
-                        .checked_mul(&amount)
-                        .ok_or(Error::<T>::Overflow)?,
-                )
+            let to_swap_amount = math::deposit_one_asset_swap_amount(
+                Self::swap_fee(&pool, fee_tier),
+                pool_origin_token_balance,
+                amount,
+            )
+            .ok_or(Error::<T>::Overflow)?;
+
+            let received_after_swap = Self::swap(
+                operator.clone(),
+                operator.clone(),
+                pool.clone(),
+                token_id,
+                to_swap_amount,
+                false,
+            )?;
+            let remaining_amount = amount
+                .checked_sub(&to_swap_amount)
                 .ok_or(Error::<T>::Overflow)?;
-            let sqrt = partial_result.integer_sqrt();
-            let to_swap_amount = sqrt
-                .checked_sub(&pool_origin_token_balance)
+
+            // Balances of the pool after the swap
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
+
+            // Because of rounding one of the assets can not be deposited in full
+            // The other one is deposited in full and the leftover is refunded
+            let recipient_pool_share = PoolShares::<T>::get(&pool, &recipient).unwrap_or_default();
+            let needed_corresponding_amount = math::deposit_corresponding_amount(
+                remaining_amount,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+            )
+            .ok_or(Error::<T>::Overflow)?;
+            let (refund_asset, refund) = if needed_corresponding_amount <= received_after_swap {
+                // The recipient receives the pool share
+                Self::dep(
+                    operator.clone(),
+                    recipient.clone(),
+                    pool.clone(),
+                    token_id,
+                    remaining_amount,
+                    false,
+                )?;
+                (
+                    corresponding_token_id,
+                    received_after_swap - needed_corresponding_amount,
+                )
+            } else {
+                Self::dep(
+                    operator.clone(),
+                    recipient.clone(),
+                    pool.clone(),
+                    corresponding_token_id,
+                    received_after_swap,
+                    false,
+                )?;
+                let needed_amount = math::deposit_corresponding_amount(
+                    received_after_swap,
+                    pool_dest_token_balance,
+                    pool_origin_token_balance,
+                )
                 .ok_or(Error::<T>::Overflow)?;
+                (token_id, remaining_amount.saturating_sub(needed_amount))
+            };
 
-            let received_after_swap =
-                Self::swap(operator.clone(), pool.clone(), token_id, to_swap_amount)?;
+            let shares = PoolShares::<T>::get(&pool, &recipient)
+                .unwrap_or_default()
+                .saturating_sub(recipient_pool_share);
+            ensure!(shares >= min_shares_out, Error::<T>::SlippageExceeded);
 
-            Self::dep(operator, pool, corresponding_token_id, received_after_swap)?;
+            Self::deposit_event(Event::<T>::DepositedOneAsset {
+                operator,
+                recipient,
+                pool_account: pool,
+                asset: token_id,
+                amount,
+                shares,
+                refund_asset,
+                refund,
+            });
 
             Ok(())
         }
 
-        #[pallet::weight(1000)]
+        #[pallet::weight(T::WeightInfo::withdraw_one_asset())]
         pub fn withdraw_one_asset(
             origin: OriginFor<T>,
             pool_address: AccountIdLookupOf<T>,
             token_id: T::AssetId,
             amount: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
         ) -> DispatchResult {
             let operator = ensure_signed(origin)?;
             let pool = T::Lookup::lookup(pool_address)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
 
             ensure!(!amount.is_zero(), Error::<T>::DepositingZeroAmount);
             ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
 
             // We have already checked that pool exists, unwrap is safe
-            let (first_asset_id, second_asset_id, _) = Self::get_pool(&pool).unwrap();
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                ..
+            } = Self::get_pool(&pool).unwrap();
             let corresponding_token_id = if token_id == first_asset_id {
                 second_asset_id
             } else if token_id == second_asset_id {
@@ -325,12 +956,9 @@ pub mod pallet {
             };
 
             // x
-            let pool_origin_token_balance =
-                T::MultiToken::get_balance(&token_id, &pool).ok_or(Error::<T>::EmptyPool)?;
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
             // y
-            let pool_dest_token_balance =
-                T::MultiToken::get_balance(&corresponding_token_id, &pool)
-                    .ok_or(Error::<T>::EmptyPool)?;
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
 
             ensure!(
                 !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
@@ -338,141 +966,1483 @@ pub mod pallet {
             );
             ensure!(pool_origin_token_balance >= amount, Error::<T>::Overflow);
 
-            let partial_result = pool_origin_token_balance
-                .checked_mul(&pool_origin_token_balance)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_sub(
-                    &pool_origin_token_balance
-                        .checked_mul(&amount)
-                        .ok_or(Error::<T>::Overflow)?,
-                )
-                .ok_or(Error::<T>::Overflow)?;
-            let sqrt = partial_result.integer_sqrt();
-            let to_withdraw_amount = pool_origin_token_balance
-                .checked_sub(&sqrt)
-                .ok_or(Error::<T>::Overflow)?;
+            let to_withdraw_amount =
+                math::withdraw_one_asset_amount(pool_origin_token_balance, amount)
+                    .ok_or(Error::<T>::Overflow)?;
 
-            let withdrawn_of_corresponding_token =
-                Self::with(operator.clone(), pool.clone(), token_id, to_withdraw_amount)?;
+            let withdrawn_of_corresponding_token = Self::with(
+                operator.clone(),
+                operator.clone(),
+                pool.clone(),
+                token_id,
+                to_withdraw_amount,
+            )?;
 
+            // Both withdrawn amounts have been received within the call, so they are moved on
+            // without keeping the account of the operator alive
             Self::swap(
-                operator,
+                operator.clone(),
+                recipient.clone(),
                 pool,
                 corresponding_token_id,
                 withdrawn_of_corresponding_token,
+                false,
             )?;
 
-            Ok(())
-        }
-    }
-
-    impl<T: Config> Pallet<T> {
-        // Checks if there is enoguh tokens on users balance
-        fn check_balance(
-            id: &T::AssetId,
-            account: &T::AccountId,
-            needed_balace: T::Balance,
-        ) -> Result<(), Error<T>> {
-            match T::MultiToken::get_balance(id, account) {
-                Some(balance) => {
-                    ensure!(needed_balace <= balance, Error::<T>::NotEnoughBalance);
-                }
-                None => {
-                    return Err(Error::<T>::NotEnoughBalance);
-                }
+            if operator != recipient {
+                Self::transfer_from_user(
+                    token_id,
+                    &operator,
+                    &recipient,
+                    to_withdraw_amount,
+                    false,
+                )?;
             }
+
             Ok(())
         }
 
-        fn swap(
-            operator: T::AccountId,
-            pool: T::AccountId,
-            token_id: T::AssetId,
-            amount: T::Balance,
-        ) -> Result<T::Balance, DispatchError> {
-            ensure!(!amount.is_zero(), Error::<T>::DepositingZeroAmount);
-            ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
-            Self::check_balance(&token_id, &operator, amount)?;
+        // Burns `shares` of the operator and pays out only `asset_out`
+        // The other asset of the withdrawn liquidity is swapped into `asset_out`
+        #[pallet::weight(T::WeightInfo::remove_liquidity_one_asset())]
+        pub fn remove_liquidity_one_asset(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+            shares: T::Balance,
+            asset_out: T::AssetId,
+            min_out: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            let operator = ensure_signed(origin)?;
+            let pool = T::Lookup::lookup(pool_address)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
 
-            // We have already checked that pool exists, unwrap is safe
-            let (first_asset_id, second_asset_id, constant) = Self::get_pool(&pool).unwrap();
-            let corresponding_token_id = if token_id == first_asset_id {
-                second_asset_id
-            } else if token_id == second_asset_id {
-                first_asset_id
-            } else {
-                ensure!(false, Error::<T>::NoSuchTokenInPool);
-                first_asset_id
-            };
+            Self::ensure_pool_unlocked(&pool)?;
+            let (corresponding_token_id, amount, corresponding_token_amount, _) =
+                Self::remove_liquidity_one_asset_amounts(&pool, shares, asset_out)?;
+            let operator_pool_share = PoolShares::<T>::get(&pool, &operator).unwrap_or_default();
+            ensure!(operator_pool_share >= shares, Error::<T>::NotEnoughShares);
 
-            let pool_origin_token_balance =
-                T::MultiToken::get_balance(&token_id, &pool).ok_or(Error::<T>::EmptyPool)?;
-            let pool_dest_token_balance =
-                T::MultiToken::get_balance(&corresponding_token_id, &pool)
-                    .ok_or(Error::<T>::EmptyPool)?;
+            // The total share is checked in `remove_liquidity_one_asset_amounts`
+            let current_full_share =
+                TotalPoolShares::<T>::get(&pool).ok_or(Error::<T>::NoSuchPool)?;
+            TotalPoolShares::<T>::set(&pool, Some(current_full_share - shares));
+            PoolShares::<T>::set(&pool, &operator, Some(operator_pool_share - shares));
+
+            Self::transfer_from_pool(asset_out, &pool, &recipient, amount)?;
+            // The last liquidity provider empties the pool, so there is nothing to swap the
+            // other asset against and it is paid out as it is
+            let is_last_provider = shares == current_full_share;
+            Self::transfer_from_pool(
+                corresponding_token_id,
+                &pool,
+                if is_last_provider {
+                    &recipient
+                } else {
+                    &operator
+                },
+                corresponding_token_amount,
+            )?;
+            Self::fetch_pool_constant(&pool, &asset_out, &corresponding_token_id)?;
 
+            let swapped_amount = if is_last_provider || corresponding_token_amount.is_zero() {
+                Zero::zero()
+            } else {
+                // The other asset has just been withdrawn to the operator
+                Self::swap(
+                    operator.clone(),
+                    recipient.clone(),
+                    pool.clone(),
+                    corresponding_token_id,
+                    corresponding_token_amount,
+                    false,
+                )?
+            };
+            let amount_out = amount
+                .checked_add(&swapped_amount)
+                .ok_or(Error::<T>::Overflow)?;
+            ensure!(amount_out >= min_out, Error::<T>::SlippageExceeded);
+
+            // Events of the withdrawal are emitted only once the whole operation has succeeded
+            Self::deposit_event(Event::<T>::Withdrawed {
+                operator: operator.clone(),
+                recipient: recipient.clone(),
+                pool_account: pool.clone(),
+                first_asset: asset_out,
+                first_asset_amount: amount,
+                second_asset: corresponding_token_id,
+                second_asset_amount: corresponding_token_amount,
+            });
+            Self::deposit_event(Event::<T>::WithdrawedOneAsset {
+                operator,
+                recipient,
+                pool_account: pool,
+                shares,
+                asset: asset_out,
+                amount: amount_out,
+            });
+
+            Ok(())
+        }
+
+        // Sends `amount` of `token_id` to the operator before it is paid for
+        // `Config::FlashSwapHandler` has to repay the pool, so that the pool constant holds
+        // including the fee, otherwise the extrinsic fails and all transfers are reverted
+        #[pallet::weight(T::WeightInfo::flash_swap())]
+        pub fn flash_swap(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+            token_id: T::AssetId,
+            amount: T::Balance,
+        ) -> DispatchResult {
+            let operator = ensure_signed(origin)?;
+            let pool = T::Lookup::lookup(pool_address)?;
+
+            ensure!(!amount.is_zero(), Error::<T>::WithdrawingZeroAmount);
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                constant,
+                status,
+                fee_tier,
+                ..
+            } = Self::get_pool(&pool).ok_or(Error::<T>::NoSuchPool)?;
+            ensure!(status == PoolStatus::Live, Error::<T>::PoolNotLive);
+            Self::ensure_pool_unlocked(&pool)?;
+            let corresponding_token_id = if token_id == first_asset_id {
+                second_asset_id
+            } else if token_id == second_asset_id {
+                first_asset_id
+            } else {
+                ensure!(false, Error::<T>::NoSuchTokenInPool);
+                first_asset_id
+            };
+
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
+            ensure!(
+                !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
+                Error::<T>::EmptyPool
+            );
+            ensure!(
+                amount < pool_origin_token_balance,
+                Error::<T>::NotEnoughBalance
+            );
+            Self::record_block_start_price(&pool, &first_asset_id, &second_asset_id);
+
+            Self::transfer_from_pool(token_id, &pool, &operator, amount)?;
+
+            // The handler could otherwise deposit at the drained ratio and count the deposit
+            // as the repayment. If it fails, the lock is reverted together with the extrinsic
+            LockedPools::<T>::insert(&pool, ());
+            T::FlashSwapHandler::on_flash_swap(
+                &operator,
+                &pool,
+                token_id,
+                amount,
+                corresponding_token_id,
+            )?;
+            LockedPools::<T>::remove(&pool);
+
+            let new_origin_token_balance = T::Assets::balance(token_id, &pool);
+            let new_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
+
+            // The fee is taken from the output the same way as in swaps,
+            // so whatever has not been returned is treated as if it was withdrawn with the fee
+            let adjusted_origin_token_balance =
+                if new_origin_token_balance < pool_origin_token_balance {
+                    let taken = pool_origin_token_balance - new_origin_token_balance;
+                    let taken_with_fee = Self::add_swap_fee(&pool, fee_tier, taken)?;
+                    pool_origin_token_balance
+                        .checked_sub(&taken_with_fee)
+                        .ok_or(Error::<T>::FlashSwapNotRepaid)?
+                } else {
+                    new_origin_token_balance
+                };
+            let adjusted_constant =
+                math::mul(adjusted_origin_token_balance, new_dest_token_balance)
+                    .ok_or(Error::<T>::Overflow)?;
+            ensure!(
+                adjusted_constant >= constant,
+                Error::<T>::FlashSwapNotRepaid
+            );
+
+            // The flash swap is a swap of the repaid asset into `token_id`, so it is bounded by
+            // the price impact and feeds the volatility the same way
+            Self::ensure_price_impact(
+                &pool,
+                pool_dest_token_balance,
+                pool_origin_token_balance,
+                new_dest_token_balance,
+                new_origin_token_balance,
+            )?;
+            Self::update_volatility(
+                &pool,
+                pool_dest_token_balance,
+                pool_origin_token_balance,
+                new_dest_token_balance,
+                new_origin_token_balance,
+            )?;
+            Self::ensure_circuit_breaker(
+                &pool,
+                T::Assets::balance(first_asset_id, &pool),
+                T::Assets::balance(second_asset_id, &pool),
+            )?;
+
+            Self::fetch_pool_constant(&pool, &token_id, &corresponding_token_id)?;
+
+            Self::deposit_event(Event::<T>::FlashSwapped {
+                operator,
+                pool_account: pool,
+                asset_out: token_id,
+                amount_out: amount,
+                asset_in: corresponding_token_id,
+                amount_in: new_dest_token_balance.saturating_sub(pool_dest_token_balance),
+            });
+
+            Ok(())
+        }
+
+        // Swaps through the route with the best output among pools of the pair index
+        // The route is searched on chain, see `Pallet::find_best_route`
+        #[pallet::weight(T::WeightInfo::swap_exact_in_auto(
+            T::MaxRouteCandidates::get(),
+            T::MaxHops::get()
+        ))]
+        pub fn swap_exact_in_auto(
+            origin: OriginFor<T>,
+            asset_in: T::AssetId,
+            asset_out: T::AssetId,
+            amount: T::Balance,
+            min_out: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            let operator = ensure_signed(origin)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
+
+            ensure!(!amount.is_zero(), Error::<T>::DepositingZeroAmount);
+            let (route, _) = Self::find_best_route(asset_in, asset_out, amount)
+                .ok_or(Error::<T>::NoRouteFound)?;
+            let amount_out = Self::swap_along_route(
+                operator.clone(),
+                recipient.clone(),
+                &route,
+                asset_in,
+                asset_out,
+                amount,
+            )?;
+            ensure!(amount_out >= min_out, Error::<T>::SlippageExceeded);
+
+            Self::deposit_event(Event::<T>::RouteSwapped {
+                operator,
+                recipient,
+                route,
+                asset_in,
+                amount_in: amount,
+                asset_out,
+                amount_out,
+            });
+
+            Ok(())
+        }
+
+        // Splits the swap across several routes, each route gets its own input amount
+        // The minimum output is checked against the sum of outputs of all routes
+        #[pallet::weight(T::WeightInfo::swap_split(routes.len() as u32, T::MaxHops::get()))]
+        pub fn swap_split(
+            origin: OriginFor<T>,
+            asset_in: T::AssetId,
+            asset_out: T::AssetId,
+            routes: BoundedVec<
+                (BoundedVec<T::AccountId, T::MaxHops>, T::Balance),
+                T::MaxSplitRoutes,
+            >,
+            min_out: T::Balance,
+            recipient: AccountIdLookupOf<T>,
+            deadline: Option<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            let operator = ensure_signed(origin)?;
+            let recipient = T::Lookup::lookup(recipient)?;
+            Self::ensure_deadline(deadline)?;
+
+            ensure!(!routes.is_empty(), Error::<T>::InvalidRoute);
+            let mut amount_in = T::Balance::zero();
+            let mut amount_out = T::Balance::zero();
+            for (route, amount) in routes.iter() {
+                let route_output = Self::swap_along_route(
+                    operator.clone(),
+                    recipient.clone(),
+                    route,
+                    asset_in,
+                    asset_out,
+                    *amount,
+                )?;
+                amount_in = amount_in.checked_add(amount).ok_or(Error::<T>::Overflow)?;
+                amount_out = amount_out
+                    .checked_add(&route_output)
+                    .ok_or(Error::<T>::Overflow)?;
+            }
+            ensure!(amount_out >= min_out, Error::<T>::SlippageExceeded);
+
+            Self::deposit_event(Event::<T>::SplitSwapped {
+                operator,
+                recipient,
+                routes: routes.len() as u32,
+                asset_in,
+                amount_in,
+                asset_out,
+                amount_out,
+            });
+
+            Ok(())
+        }
+
+        // Executes deposits, withdrawals and swaps of the caller all-or-nothing
+        // Every operation emits its usual event followed by `BatchOperationCompleted`
+        #[pallet::weight(Pallet::<T>::batch_liquidity_weight(operations))]
+        pub fn batch_liquidity(
+            origin: OriginFor<T>,
+            operations: BoundedVec<LiquidityOperationOf<T>, T::MaxBatchOperations>,
+            deadline: Option<BlockNumberFor<T>>,
+        ) -> DispatchResult {
+            let operator = ensure_signed(origin)?;
+            Self::ensure_deadline(deadline)?;
+
+            let keep_alive = !Self::is_death_allowed(&operator);
+            for (index, operation) in operations.iter().cloned().enumerate() {
+                let amount = match operation {
+                    LiquidityOperation::Add {
+                        pool,
+                        token_id,
+                        amount,
+                    } => Self::dep(
+                        operator.clone(),
+                        operator.clone(),
+                        pool,
+                        token_id,
+                        amount,
+                        keep_alive,
+                    )?,
+                    LiquidityOperation::Remove {
+                        pool,
+                        token_id,
+                        amount,
+                    } => Self::with(operator.clone(), operator.clone(), pool, token_id, amount)?,
+                    LiquidityOperation::Swap {
+                        pool,
+                        token_id,
+                        amount,
+                    } => Self::swap(
+                        operator.clone(),
+                        operator.clone(),
+                        pool,
+                        token_id,
+                        amount,
+                        keep_alive,
+                    )?,
+                };
+                Self::deposit_event(Event::<T>::BatchOperationCompleted {
+                    index: index as u32,
+                    amount,
+                });
+            }
+
+            Self::deposit_event(Event::<T>::BatchCompleted {
+                operator,
+                operations: operations.len() as u32,
+            });
+            Ok(())
+        }
+
+        #[pallet::weight(T::WeightInfo::add_fee_tier())]
+        pub fn add_fee_tier(origin: OriginFor<T>, fee_tier: Permill) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                !Self::is_fee_tier_allowed(&fee_tier),
+                Error::<T>::FeeTierAlreadyExists
+            );
+            FeeTiers::<T>::insert(&fee_tier, ());
+
+            Self::deposit_event(Event::<T>::FeeTierAdded { fee_tier });
+
+            Ok(())
+        }
+
+        // Existing pools of the tier are not affected
+        // The default fee tier is not stored in `FeeTiers` and can not be removed
+        #[pallet::weight(T::WeightInfo::remove_fee_tier())]
+        pub fn remove_fee_tier(origin: OriginFor<T>, fee_tier: Permill) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                fee_tier != Self::default_fee_tier(),
+                Error::<T>::DefaultFeeTierNotRemovable
+            );
+            ensure!(
+                Self::get_fee_tier(&fee_tier) != None,
+                Error::<T>::NoSuchFeeTier
+            );
+            FeeTiers::<T>::remove(&fee_tier);
+
+            Self::deposit_event(Event::<T>::FeeTierRemoved { fee_tier });
+
+            Ok(())
+        }
+
+        #[pallet::weight(T::WeightInfo::list_asset())]
+        pub fn list_asset(origin: OriginFor<T>, asset_id: T::AssetId) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                Self::get_allowed_asset(&asset_id) == None,
+                Error::<T>::AssetAlreadyListed
+            );
+            AllowedAssets::<T>::insert(&asset_id, ());
+
+            Self::deposit_event(Event::<T>::AssetListed { asset: asset_id });
+
+            Ok(())
+        }
+
+        #[pallet::weight(T::WeightInfo::delist_asset())]
+        pub fn delist_asset(origin: OriginFor<T>, asset_id: T::AssetId) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            ensure!(
+                Self::get_allowed_asset(&asset_id) != None,
+                Error::<T>::AssetNotListed
+            );
+            AllowedAssets::<T>::remove(&asset_id);
+
+            Self::deposit_event(Event::<T>::AssetDelisted { asset: asset_id });
+
+            Ok(())
+        }
+
+        #[pallet::weight(T::WeightInfo::set_allow_list_enabled())]
+        pub fn set_allow_list_enabled(origin: OriginFor<T>, enabled: bool) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            AllowListEnabled::<T>::put(enabled);
+
+            Self::deposit_event(Event::<T>::AllowListToggled { enabled });
+
+            Ok(())
+        }
+
+        // Lets transfers from the caller to the pools drop the caller below the minimum balance
+        // of an asset, in which case the rest of it is lost as dust
+        #[pallet::weight(T::WeightInfo::set_allow_death())]
+        pub fn set_allow_death(origin: OriginFor<T>, allow_death: bool) -> DispatchResult {
+            let account = ensure_signed(origin)?;
+
+            if allow_death {
+                AllowDeath::<T>::insert(&account, ());
+            } else {
+                AllowDeath::<T>::remove(&account);
+            }
+
+            Self::deposit_event(Event::<T>::AllowDeathSet {
+                account,
+                allow_death,
+            });
+
+            Ok(())
+        }
+
+        // Sets the bound that is used for pools without their own one, `None` removes it
+        #[pallet::weight(T::WeightInfo::set_default_max_price_impact())]
+        pub fn set_default_max_price_impact(
+            origin: OriginFor<T>,
+            max_price_impact: Option<Permill>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            DefaultMaxPriceImpact::<T>::set(max_price_impact);
+
+            Self::deposit_event(Event::<T>::MaxPriceImpactSet {
+                pool_account: None,
+                max_price_impact,
+            });
+
+            Ok(())
+        }
+
+        // Sets the bound of a single pool, `None` makes the pool use the default one
+        #[pallet::weight(T::WeightInfo::set_pool_max_price_impact())]
+        pub fn set_pool_max_price_impact(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+            max_price_impact: Option<Permill>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let pool = T::Lookup::lookup(pool_address)?;
+
+            ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
+            PoolMaxPriceImpact::<T>::set(&pool, max_price_impact);
+
+            Self::deposit_event(Event::<T>::MaxPriceImpactSet {
+                pool_account: Some(pool),
+                max_price_impact,
+            });
+
+            Ok(())
+        }
+
+        // Makes the pool fee follow the volatility of the pool between the given bounds
+        // `None` returns the pool to the fee from `Config`
+        #[pallet::weight(T::WeightInfo::set_dynamic_fee())]
+        pub fn set_dynamic_fee(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+            fee_bounds: Option<(Permill, Permill)>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let pool = T::Lookup::lookup(pool_address)?;
+
+            ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
+            match fee_bounds {
+                Some((min_fee, max_fee)) => {
+                    ensure!(min_fee <= max_fee, Error::<T>::InvalidFeeBounds);
+                    DynamicFees::<T>::insert(
+                        &pool,
+                        DynamicFee {
+                            min_fee,
+                            max_fee,
+                            volatility: Zero::zero(),
+                        },
+                    );
+                }
+                None => DynamicFees::<T>::remove(&pool),
+            }
+
+            Self::deposit_event(Event::<T>::DynamicFeeSet {
+                pool_account: pool,
+                fee_bounds,
+            });
+
+            Ok(())
+        }
+
+        // Sets the maximum price move of a pool in one block, `None` disables the circuit breaker
+        #[pallet::weight(T::WeightInfo::set_circuit_breaker_threshold())]
+        pub fn set_circuit_breaker_threshold(
+            origin: OriginFor<T>,
+            threshold: Option<Permill>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+
+            CircuitBreakerThreshold::<T>::set(threshold);
+
+            Self::deposit_event(Event::<T>::CircuitBreakerThresholdSet { threshold });
+
+            Ok(())
+        }
+
+        #[pallet::weight(T::WeightInfo::freeze_pool())]
+        pub fn freeze_pool(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let pool = T::Lookup::lookup(pool_address)?;
+
+            let mut pool_info = Self::get_pool(&pool).ok_or(Error::<T>::NoSuchPool)?;
+            ensure!(
+                pool_info.status == PoolStatus::Live,
+                Error::<T>::PoolNotLive
+            );
+            pool_info.status = PoolStatus::Frozen;
+            Pools::<T>::insert(&pool, pool_info);
+
+            Self::deposit_event(Event::<T>::PoolFrozen { pool_account: pool });
+
+            Ok(())
+        }
+
+        #[pallet::weight(T::WeightInfo::thaw_pool())]
+        pub fn thaw_pool(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+        ) -> DispatchResult {
+            T::AdminOrigin::ensure_origin(origin)?;
+            let pool = T::Lookup::lookup(pool_address)?;
+
+            let mut pool_info = Self::get_pool(&pool).ok_or(Error::<T>::NoSuchPool)?;
+            ensure!(
+                pool_info.status == PoolStatus::Frozen,
+                Error::<T>::PoolNotFrozen
+            );
+            pool_info.status = PoolStatus::Live;
+            Pools::<T>::insert(&pool, pool_info);
+
+            Self::deposit_event(Event::<T>::PoolThawed { pool_account: pool });
+
+            Ok(())
+        }
+
+        // Removes the pool in batches of `Config::DestroyBatchSize` shares per call
+        // Anyone can destroy a pool without shares, the admin can also destroy a frozen pool,
+        // in which case liquidity providers are paid out their share of both assets
+        #[pallet::weight(T::WeightInfo::destroy_pool(T::DestroyBatchSize::get()))]
+        pub fn destroy_pool(
+            origin: OriginFor<T>,
+            pool_address: AccountIdLookupOf<T>,
+        ) -> DispatchResult {
+            let is_admin = match T::AdminOrigin::try_origin(origin) {
+                Ok(_) => true,
+                Err(origin) => {
+                    ensure_signed(origin)?;
+                    false
+                }
+            };
+            let pool = T::Lookup::lookup(pool_address)?;
+
+            let mut pool_info = Self::get_pool(&pool).ok_or(Error::<T>::NoSuchPool)?;
+            let mut total_shares = Self::get_total_pool_shares(&pool).unwrap_or_else(Zero::zero);
+            ensure!(
+                pool_info.status == PoolStatus::Destroying
+                    || total_shares.is_zero()
+                    || (is_admin && pool_info.status == PoolStatus::Frozen),
+                Error::<T>::PoolNotDestroyable
+            );
+
+            // Shares are removed as they are paid out, so the next call continues with the rest
+            let batch =
+                PoolShares::<T>::drain_prefix(&pool).take(T::DestroyBatchSize::get() as usize);
+            for (provider, shares) in batch {
+                if shares.is_zero() {
+                    continue;
+                }
+                Self::pay_out_shares(
+                    &pool,
+                    &provider,
+                    &pool_info.first_asset,
+                    shares,
+                    total_shares,
+                )?;
+                Self::pay_out_shares(
+                    &pool,
+                    &provider,
+                    &pool_info.second_asset,
+                    shares,
+                    total_shares,
+                )?;
+                total_shares = total_shares
+                    .checked_sub(&shares)
+                    .ok_or(Error::<T>::Overflow)?;
+            }
+            TotalPoolShares::<T>::insert(&pool, total_shares);
+            if PoolShares::<T>::iter_prefix(&pool).next().is_some() {
+                pool_info.status = PoolStatus::Destroying;
+                Pools::<T>::insert(&pool, pool_info);
+                return Ok(());
+            }
+
+            let first_asset_dust = Self::sweep_dust(&pool, &pool_info.first_asset)?;
+            let second_asset_dust = Self::sweep_dust(&pool, &pool_info.second_asset)?;
+            Self::release_creation_deposit(&pool_info);
+            // The account still holding dust that could not be swept keeps its provider
+            if T::Assets::balance(pool_info.first_asset, &pool).is_zero()
+                && T::Assets::balance(pool_info.second_asset, &pool).is_zero()
+            {
+                frame_system::Pallet::<T>::dec_providers(&pool)?;
+            }
+
+            Pools::<T>::remove(&pool);
+            // Pools migrated from before version 1 may share the pair and fee tier with another
+            // pool, the index entry is only removed if it points to this one
+            let pair = Self::pair_key(pool_info.first_asset, pool_info.second_asset);
+            if PoolIndex::<T>::get(&pair, &pool_info.fee_tier).as_ref() == Some(&pool) {
+                PoolIndex::<T>::remove(&pair, &pool_info.fee_tier);
+            }
+            AssetPools::<T>::remove(&pool_info.first_asset, &pool);
+            AssetPools::<T>::remove(&pool_info.second_asset, &pool);
+            TotalPoolShares::<T>::remove(&pool);
+            PoolMaxPriceImpact::<T>::remove(&pool);
+            BlockStartPrices::<T>::remove(&pool);
+            DynamicFees::<T>::remove(&pool);
+
+            Self::deposit_event(Event::<T>::PoolDestroyed {
+                pool_account: pool,
+                first_asset: pool_info.first_asset,
+                first_asset_dust,
+                second_asset: pool_info.second_asset,
+                second_asset_dust,
+            });
+
+            Ok(())
+        }
+    }
+
+    impl<T: Config> Pallet<T> {
+        // Checks the invariants of every pool that is not being destroyed
+        // - `TotalPoolShares` equals the sum of `PoolShares`
+        // - the pool holds at least the stored constant
+        // The constant is recomputed from the balances after every operation of the pallet, fees
+        // and rounding included, so it is not an equality only because anyone can transfer tokens
+        // to the pool account directly, which raises the product without touching the constant
+        // This version of FRAME has no `Hooks::try_state`, so it is called from `post_upgrade`
+        #[cfg(any(test, feature = "fuzzing", feature = "try-runtime"))]
+        pub fn do_try_state() -> Result<(), &'static str> {
+            for (pool, pool_info) in Pools::<T>::iter() {
+                if pool_info.status == PoolStatus::Destroying {
+                    continue;
+                }
+
+                let total_shares =
+                    TotalPoolShares::<T>::get(&pool).ok_or("Pool has no total share")?;
+                let mut shares = T::Balance::zero();
+                for share in PoolShares::<T>::iter_prefix_values(&pool) {
+                    shares = shares.checked_add(&share).ok_or("Pool shares overflow")?;
+                }
+                ensure!(
+                    shares == total_shares,
+                    "Total pool share does not match the sum of pool shares"
+                );
+
+                let first_asset_balance = T::Assets::balance(pool_info.first_asset, &pool);
+                let second_asset_balance = T::Assets::balance(pool_info.second_asset, &pool);
+                let product = math::mul(first_asset_balance, second_asset_balance)
+                    .ok_or("Pool balances overflow")?;
+                ensure!(
+                    pool_info.constant <= product,
+                    "Pool constant is higher than the product of pool balances"
+                );
+            }
+            Ok(())
+        }
+
+        // Creates a pool, shared by `init` and the genesis config
+        pub(crate) fn do_init(
+            creator: T::AccountId,
+            pool: T::AccountId,
+            first_token_id: T::AssetId,
+            first_token_amount: T::Balance,
+            second_token_id: T::AssetId,
+            second_token_amount: T::Balance,
+            fee_tier: Permill,
+        ) -> DispatchResult {
+            ensure!(
+                !first_token_amount.is_zero() && !second_token_amount.is_zero(),
+                Error::<T>::DepositingZeroAmount
+            );
+            ensure!(Self::get_pool(&pool) == None, Error::<T>::PoolAlreadyExists);
+            ensure!(first_token_id != second_token_id, Error::<T>::SameAssetPool);
+            let pair = Self::pair_key(first_token_id, second_token_id);
+            ensure!(
+                Self::get_pool_by_pair(&pair, &fee_tier) == None,
+                Error::<T>::PoolAlreadyExists
+            );
+            ensure!(
+                Self::is_fee_tier_allowed(&fee_tier),
+                Error::<T>::FeeTierNotAllowed
+            );
+            Self::ensure_asset_allowed(&first_token_id)?;
+            Self::ensure_asset_allowed(&second_token_id)?;
+            let keep_alive = !Self::is_death_allowed(&creator);
+            Self::check_balance(&first_token_id, &creator, first_token_amount, keep_alive)?;
+            Self::check_balance(&second_token_id, &creator, second_token_amount, keep_alive)?;
+
+            let pool_constant =
+                math::mul(first_token_amount, second_token_amount).ok_or(Error::<T>::Overflow)?;
+
+            let deposit = T::PoolCreationDeposit::get();
+            T::Currency::reserve(&creator, deposit)
+                .map_err(|_| Error::<T>::NotEnoughBalanceForDeposit)?;
+
+            // Keeps the pool account alive while it holds liquidity and lets it receive assets
+            // that are not sufficient
+            frame_system::Pallet::<T>::inc_providers(&pool);
+
+            Self::transfer_from_user(
+                first_token_id,
+                &creator,
+                &pool,
+                first_token_amount,
+                keep_alive,
+            )?;
+
+            Self::transfer_from_user(
+                second_token_id,
+                &creator,
+                &pool,
+                second_token_amount,
+                keep_alive,
+            )?;
+
+            Pools::<T>::insert(
+                &pool,
+                PoolInfo {
+                    first_asset: first_token_id,
+                    second_asset: second_token_id,
+                    constant: pool_constant,
+                    creator: creator.clone(),
+                    deposit,
+                    status: PoolStatus::Live,
+                    fee_tier,
+                },
+            );
+            PoolIndex::<T>::insert(&pair, &fee_tier, &pool);
+            AssetPools::<T>::insert(&first_token_id, &pool, ());
+            AssetPools::<T>::insert(&second_token_id, &pool, ());
+            PoolShares::<T>::insert(&pool, &creator, T::DefaultShare::get());
+            TotalPoolShares::<T>::insert(&pool, T::DefaultShare::get());
+
+            Self::deposit_event(Event::<T>::PoolCreated {
+                creator,
+                pool_account: pool,
+                first_asset: first_token_id,
+                second_asset: second_token_id,
+                fee_tier,
+            });
+
+            Ok(())
+        }
+
+        // Checks that a trading extrinsic is executed before its deadline
+        // Should be called before any pool math
+        fn ensure_deadline(deadline: Option<BlockNumberFor<T>>) -> Result<(), Error<T>> {
+            if let Some(deadline) = deadline {
+                ensure!(
+                    frame_system::Pallet::<T>::block_number() <= deadline,
+                    Error::<T>::DeadlinePassed
+                );
+            }
+            Ok(())
+        }
+
+        // Returns the output of swapping `amount` into the pool after the fee and the fee rate
+        fn calculate_swap_output(
+            pool: &T::AccountId,
+            fee_tier: Permill,
+            constant: U256,
+            pool_origin_token_balance: T::Balance,
+            pool_dest_token_balance: T::Balance,
+            amount: T::Balance,
+        ) -> Result<(T::Balance, Permill), Error<T>> {
+            let fee = Self::swap_fee(pool, fee_tier);
+            let output = math::quote_out(
+                constant,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+                amount,
+                fee,
+            )
+            .ok_or(Error::<T>::Overflow)?;
+            Ok((output, fee))
+        }
+
+        // Returns the fee of the next swap in the pool
+        fn swap_fee(pool: &T::AccountId, fee_tier: Permill) -> Permill {
+            match Self::get_dynamic_fee(pool) {
+                Some(dynamic_fee) => dynamic_fee.current_fee(),
+                None => fee_tier,
+            }
+        }
+
+        // The reverse of taking the swap fee, returns the output before the fee (rounded up)
+        fn add_swap_fee(
+            pool: &T::AccountId,
+            fee_tier: Permill,
+            output: T::Balance,
+        ) -> Result<T::Balance, Error<T>> {
+            math::amount_before_fee(output, Self::swap_fee(pool, fee_tier))
+                .ok_or(Error::<T>::Overflow)
+        }
+
+        // The fee from `Config` is always an allowed fee tier
+        pub fn default_fee_tier() -> Permill {
+            Permill::from_rational(
+                T::HundredPercent::get().saturating_sub(T::HundredPercentMinusFee::get()),
+                T::HundredPercent::get(),
+            )
+        }
+
+        fn is_fee_tier_allowed(fee_tier: &Permill) -> bool {
+            *fee_tier == Self::default_fee_tier() || Self::get_fee_tier(fee_tier) != None
+        }
+
+        pub fn is_death_allowed(account: &T::AccountId) -> bool {
+            AllowDeath::<T>::contains_key(account)
+        }
+
+        // Pools are indexed by their pair of assets in ascending order
+        pub fn pair_key(
+            first_asset: T::AssetId,
+            second_asset: T::AssetId,
+        ) -> (T::AssetId, T::AssetId) {
+            if first_asset < second_asset {
+                (first_asset, second_asset)
+            } else {
+                (second_asset, first_asset)
+            }
+        }
+
+        // Returns the asset that the swap gives, the output of the swap and the fee rate
+        // Does not check price impact and circuit breaker bounds
+        pub fn quote_exact_in(
+            pool: &T::AccountId,
+            token_id: T::AssetId,
+            amount: T::Balance,
+        ) -> Result<(T::AssetId, T::Balance, Permill), DispatchError> {
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                constant,
+                status,
+                fee_tier,
+                ..
+            } = Self::get_pool(pool).ok_or(Error::<T>::NoSuchPool)?;
+            ensure!(status == PoolStatus::Live, Error::<T>::PoolNotLive);
+            let corresponding_token_id = if token_id == first_asset_id {
+                second_asset_id
+            } else if token_id == second_asset_id {
+                first_asset_id
+            } else {
+                ensure!(false, Error::<T>::NoSuchTokenInPool);
+                first_asset_id
+            };
+
+            let pool_origin_token_balance = T::Assets::balance(token_id, pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, pool);
             ensure!(
                 !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
                 Error::<T>::EmptyPool
             );
 
-            let partial_calculation = constant
-                .checked_div(
-                    &pool_origin_token_balance
-                        .checked_add(&amount)
-                        .ok_or(Error::<T>::Overflow)?,
-                )
+            let (output, fee) = Self::calculate_swap_output(
+                pool,
+                fee_tier,
+                constant,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+                amount,
+            )?;
+            Ok((corresponding_token_id, output, fee))
+        }
+
+        // Returns the amount of `asset_out` that `remove_liquidity_one_asset` pays out for `shares`
+        // including the swap fee
+        pub fn quote_remove_liquidity_one_asset(
+            pool: &T::AccountId,
+            shares: T::Balance,
+            asset_out: T::AssetId,
+        ) -> Result<T::Balance, DispatchError> {
+            let (_, amount, _, swapped_amount) =
+                Self::remove_liquidity_one_asset_amounts(pool, shares, asset_out)?;
+            Ok(amount
+                .checked_add(&swapped_amount)
+                .ok_or(Error::<T>::Overflow)?)
+        }
+
+        // Returns the other asset of the pool, the withdrawn amounts of both assets for `shares`
+        // and the output of swapping the other asset into `asset_out` after the withdrawal
+        fn remove_liquidity_one_asset_amounts(
+            pool: &T::AccountId,
+            shares: T::Balance,
+            asset_out: T::AssetId,
+        ) -> Result<(T::AssetId, T::Balance, T::Balance, T::Balance), DispatchError> {
+            ensure!(!shares.is_zero(), Error::<T>::WithdrawingZeroAmount);
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                status,
+                fee_tier,
+                ..
+            } = Self::get_pool(pool).ok_or(Error::<T>::NoSuchPool)?;
+            ensure!(status != PoolStatus::Destroying, Error::<T>::PoolNotLive);
+            let corresponding_token_id = if asset_out == first_asset_id {
+                second_asset_id
+            } else if asset_out == second_asset_id {
+                first_asset_id
+            } else {
+                ensure!(false, Error::<T>::NoSuchTokenInPool);
+                first_asset_id
+            };
+
+            let pool_origin_token_balance = T::Assets::balance(asset_out, pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, pool);
+            let current_full_share =
+                TotalPoolShares::<T>::get(pool).ok_or(Error::<T>::NoSuchPool)?;
+            ensure!(shares <= current_full_share, Error::<T>::NotEnoughShares);
+
+            let amount = math::share_value(shares, pool_origin_token_balance, current_full_share)
                 .ok_or(Error::<T>::Overflow)?;
-            let swap_token_result = pool_dest_token_balance
-                .checked_sub(&partial_calculation)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_mul(&T::HundredPercentMinusFee::get())
-                .ok_or(Error::<T>::Overflow)?
-                .checked_div(&T::HundredPercent::get())
+            let corresponding_token_amount =
+                math::share_value(shares, pool_dest_token_balance, current_full_share)
+                    .ok_or(Error::<T>::Overflow)?;
+            if corresponding_token_amount.is_zero() {
+                return Ok((corresponding_token_id, amount, Zero::zero(), Zero::zero()));
+            }
+            // The last liquidity provider receives both assets without the swap
+            if shares == current_full_share {
+                return Ok((
+                    corresponding_token_id,
+                    amount,
+                    corresponding_token_amount,
+                    Zero::zero(),
+                ));
+            }
+
+            // The swap happens against the pool without the withdrawn liquidity
+            let pool_origin_token_balance = pool_origin_token_balance - amount;
+            let pool_dest_token_balance = pool_dest_token_balance - corresponding_token_amount;
+            ensure!(
+                !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
+                Error::<T>::EmptyPool
+            );
+            let (swapped_amount, _) = Self::calculate_swap_output(
+                pool,
+                fee_tier,
+                math::mul(pool_origin_token_balance, pool_dest_token_balance)
+                    .ok_or(Error::<T>::Overflow)?,
+                pool_dest_token_balance,
+                pool_origin_token_balance,
+                corresponding_token_amount,
+            )?;
+            Ok((
+                corresponding_token_id,
+                amount,
+                corresponding_token_amount,
+                swapped_amount,
+            ))
+        }
+
+        // The weight of `batch_liquidity` is the sum of weights of the operations
+        pub fn batch_liquidity_weight(operations: &[LiquidityOperationOf<T>]) -> Weight {
+            operations.iter().fold(
+                T::WeightInfo::batch_liquidity(operations.len() as u32),
+                |weight, operation| {
+                    weight.saturating_add(match operation {
+                        LiquidityOperation::Add { .. } => T::WeightInfo::deposit(),
+                        LiquidityOperation::Remove { .. } => T::WeightInfo::withdraw(),
+                        LiquidityOperation::Swap { .. } => T::WeightInfo::swap_token(),
+                    })
+                },
+            )
+        }
+
+        // Searches routes of up to `Config::MaxHops` pools from `asset_in` to `asset_out`
+        // and returns the one with the best output together with the output
+        // At most `Config::MaxRouteCandidates` pools are quoted, so the search is weight-bounded
+        pub fn find_best_route(
+            asset_in: T::AssetId,
+            asset_out: T::AssetId,
+            amount: T::Balance,
+        ) -> Option<(Vec<T::AccountId>, T::Balance)> {
+            let mut best_route = None;
+            let mut route = Vec::new();
+            let mut visited_assets = Vec::from([asset_in]);
+            let mut candidates_left = T::MaxRouteCandidates::get();
+            Self::search_routes(
+                asset_in,
+                asset_out,
+                amount,
+                &mut route,
+                &mut visited_assets,
+                &mut best_route,
+                &mut candidates_left,
+            );
+            best_route
+        }
+
+        // Depth-first search of routes, `route` and `visited_assets` hold the current path
+        fn search_routes(
+            asset: T::AssetId,
+            asset_out: T::AssetId,
+            amount: T::Balance,
+            route: &mut Vec<T::AccountId>,
+            visited_assets: &mut Vec<T::AssetId>,
+            best_route: &mut Option<(Vec<T::AccountId>, T::Balance)>,
+            candidates_left: &mut u32,
+        ) {
+            if route.len() as u32 >= T::MaxHops::get() {
+                return;
+            }
+            for (pool, ()) in AssetPools::<T>::iter_prefix(asset) {
+                if candidates_left.is_zero() {
+                    return;
+                }
+                *candidates_left -= 1;
+
+                let (next_asset, output) = match Self::quote_exact_in(&pool, asset, amount) {
+                    Ok((next_asset, output, _)) if !output.is_zero() => (next_asset, output),
+                    _ => continue,
+                };
+                if visited_assets.contains(&next_asset) {
+                    continue;
+                }
+
+                route.push(pool);
+                if next_asset == asset_out {
+                    if best_route
+                        .as_ref()
+                        .map_or(true, |(_, best_output)| output > *best_output)
+                    {
+                        *best_route = Some((route.clone(), output));
+                    }
+                } else {
+                    visited_assets.push(next_asset);
+                    Self::search_routes(
+                        next_asset,
+                        asset_out,
+                        output,
+                        route,
+                        visited_assets,
+                        best_route,
+                        candidates_left,
+                    );
+                    visited_assets.pop();
+                }
+                route.pop();
+            }
+        }
+
+        // Swaps `amount` of `asset_in` through every pool of the route
+        // Intermediate assets stay with the operator, the recipient receives the last output
+        // Only the first hop spends the own balance of the operator, the following ones spend
+        // what the previous hop has paid out, so they do not keep the account alive
+        fn swap_along_route(
+            operator: T::AccountId,
+            recipient: T::AccountId,
+            route: &[T::AccountId],
+            asset_in: T::AssetId,
+            asset_out: T::AssetId,
+            amount: T::Balance,
+        ) -> Result<T::Balance, DispatchError> {
+            ensure!(!route.is_empty(), Error::<T>::InvalidRoute);
+            let mut asset = asset_in;
+            let mut amount = amount;
+            for (index, pool) in route.iter().enumerate() {
+                let pool_info = Self::get_pool(pool).ok_or(Error::<T>::NoSuchPool)?;
+                let next_asset = if asset == pool_info.first_asset {
+                    pool_info.second_asset
+                } else {
+                    pool_info.first_asset
+                };
+                let hop_recipient = if index + 1 == route.len() {
+                    recipient.clone()
+                } else {
+                    operator.clone()
+                };
+                let keep_alive = index == 0 && !Self::is_death_allowed(&operator);
+                amount = Self::swap(
+                    operator.clone(),
+                    hop_recipient,
+                    pool.clone(),
+                    asset,
+                    amount,
+                    keep_alive,
+                )?;
+                asset = next_asset;
+            }
+            ensure!(asset == asset_out, Error::<T>::InvalidRoute);
+            Ok(amount)
+        }
+
+        // Picks the fee tier of the pair that gives the most of `asset_out` for `amount`
+        // Returns the pool address and the output of the swap
+        pub fn best_pool_for_pair(
+            asset_in: T::AssetId,
+            asset_out: T::AssetId,
+            amount: T::Balance,
+        ) -> Option<(T::AccountId, T::Balance)> {
+            PoolIndex::<T>::iter_prefix(Self::pair_key(asset_in, asset_out))
+                .filter_map(|(_, pool)| {
+                    Self::quote_exact_in(&pool, asset_in, amount)
+                        .ok()
+                        .map(|(_, output, _)| (pool, output))
+                })
+                .max_by_key(|(_, output)| *output)
+        }
+
+        // Updates the moving average of price changes for pools with dynamic fees
+        fn update_volatility(
+            pool: &T::AccountId,
+            origin_balance_before: T::Balance,
+            dest_balance_before: T::Balance,
+            origin_balance_after: T::Balance,
+            dest_balance_after: T::Balance,
+        ) -> Result<(), Error<T>> {
+            let mut dynamic_fee = match Self::get_dynamic_fee(pool) {
+                Some(dynamic_fee) => dynamic_fee,
+                None => return Ok(()),
+            };
+
+            let price_change = math::price_impact(
+                origin_balance_before,
+                dest_balance_before,
+                origin_balance_after,
+                dest_balance_after,
+            )
+            .ok_or(Error::<T>::Overflow)?;
+
+            let smoothing = T::VolatilitySmoothing::get();
+            dynamic_fee.volatility = (smoothing * price_change)
+                .saturating_add(smoothing.left_from_one() * dynamic_fee.volatility);
+            DynamicFees::<T>::insert(pool, dynamic_fee);
+            Ok(())
+        }
+
+        // Checks that the spot price (y / x) after the swap has not moved by more than
+        // `PoolMaxPriceImpact` or `DefaultMaxPriceImpact` of the pool
+        fn ensure_price_impact(
+            pool: &T::AccountId,
+            origin_balance_before: T::Balance,
+            dest_balance_before: T::Balance,
+            origin_balance_after: T::Balance,
+            dest_balance_after: T::Balance,
+        ) -> Result<(), Error<T>> {
+            let max_price_impact = match Self::get_pool_max_price_impact(pool)
+                .or_else(Self::get_default_max_price_impact)
+            {
+                Some(max_price_impact) => max_price_impact,
+                None => return Ok(()),
+            };
+
+            let within_price_impact = math::is_within_price_impact(
+                origin_balance_before,
+                dest_balance_before,
+                origin_balance_after,
+                dest_balance_after,
+                max_price_impact,
+            )
+            .ok_or(Error::<T>::Overflow)?;
+            ensure!(within_price_impact, Error::<T>::PriceImpactTooHigh);
+            Ok(())
+        }
+
+        // Remembers the balances of the pool on the first swap in a block
+        fn record_block_start_price(
+            pool: &T::AccountId,
+            first_asset_id: &T::AssetId,
+            second_asset_id: &T::AssetId,
+        ) {
+            if Self::get_circuit_breaker_threshold() == None {
+                return;
+            }
+
+            let now = frame_system::Pallet::<T>::block_number();
+            if Self::get_block_start_price(pool).map(|start_price| start_price.block) != Some(now) {
+                BlockStartPrices::<T>::insert(
+                    pool,
+                    BlockStartPrice {
+                        block: now,
+                        first_asset_balance: T::Assets::balance(*first_asset_id, pool),
+                        second_asset_balance: T::Assets::balance(*second_asset_id, pool),
+                    },
+                );
+            }
+        }
+
+        // Fails if the price given by the balances of the pool after a swap has moved by more
+        // than `CircuitBreakerThreshold` since the first swap in the current block
+        // It is checked before anything is transferred, so no swap can cross the threshold
+        fn ensure_circuit_breaker(
+            pool: &T::AccountId,
+            first_asset_balance: T::Balance,
+            second_asset_balance: T::Balance,
+        ) -> Result<(), Error<T>> {
+            let threshold = match Self::get_circuit_breaker_threshold() {
+                Some(threshold) => threshold,
+                None => return Ok(()),
+            };
+            let start_price = match Self::get_block_start_price(pool) {
+                Some(start_price) => start_price,
+                None => return Ok(()),
+            };
+
+            // The price is y / x, so the move is |y * x0 - y0 * x| / (y0 * x)
+            let current_price = math::mul(second_asset_balance, start_price.first_asset_balance)
                 .ok_or(Error::<T>::Overflow)?;
+            let start_price_value =
+                math::mul(start_price.second_asset_balance, first_asset_balance)
+                    .ok_or(Error::<T>::Overflow)?;
+            let price_move = if current_price > start_price_value {
+                current_price - start_price_value
+            } else {
+                start_price_value - current_price
+            };
 
-            T::MultiToken::safe_transfer(
-                operator.clone(),
-                operator.clone(),
-                pool.clone(),
-                token_id,
+            ensure!(
+                math::cmp_part(start_price_value, threshold, price_move) != Ordering::Less,
+                Error::<T>::CircuitBreakerActive
+            );
+            Ok(())
+        }
+
+        // Checks that the reserves of the pool are not held by a flash swap or a flash loan
+        fn ensure_pool_unlocked(pool: &T::AccountId) -> Result<(), Error<T>> {
+            ensure!(
+                !LockedPools::<T>::contains_key(pool),
+                Error::<T>::PoolLocked
+            );
+            Ok(())
+        }
+
+        // Checks that an asset can be used in a new pool
+        // Every asset is allowed while the allow-list is disabled
+        fn ensure_asset_allowed(id: &T::AssetId) -> Result<(), Error<T>> {
+            if Self::is_allow_list_enabled() {
+                ensure!(
+                    Self::get_allowed_asset(id) != None,
+                    Error::<T>::AssetNotAllowed
+                );
+            }
+            Ok(())
+        }
+
+        // Checks if there is enoguh tokens on users balance
+        // `keep_alive` leaves the minimum balance of the asset out
+        fn check_balance(
+            id: &T::AssetId,
+            account: &T::AccountId,
+            needed_balace: T::Balance,
+            keep_alive: bool,
+        ) -> Result<(), Error<T>> {
+            ensure!(
+                needed_balace <= T::Assets::reducible_balance(*id, account, keep_alive),
+                Error::<T>::NotEnoughBalance
+            );
+            Ok(())
+        }
+
+        // Sends the result of the swap to the recipient
+        // `keep_alive` is passed to `transfer_from_user` for the input
+        fn swap(
+            operator: T::AccountId,
+            recipient: T::AccountId,
+            pool: T::AccountId,
+            token_id: T::AssetId,
+            amount: T::Balance,
+            keep_alive: bool,
+        ) -> Result<T::Balance, DispatchError> {
+            ensure!(!amount.is_zero(), Error::<T>::DepositingZeroAmount);
+            ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
+            Self::ensure_pool_unlocked(&pool)?;
+            Self::check_balance(&token_id, &operator, amount, keep_alive)?;
+
+            // We have already checked that pool exists, unwrap is safe
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                constant,
+                status,
+                fee_tier,
+                ..
+            } = Self::get_pool(&pool).unwrap();
+            ensure!(status == PoolStatus::Live, Error::<T>::PoolNotLive);
+            let corresponding_token_id = if token_id == first_asset_id {
+                second_asset_id
+            } else if token_id == second_asset_id {
+                first_asset_id
+            } else {
+                ensure!(false, Error::<T>::NoSuchTokenInPool);
+                first_asset_id
+            };
+
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
+
+            ensure!(
+                !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
+                Error::<T>::EmptyPool
+            );
+            Self::record_block_start_price(&pool, &first_asset_id, &second_asset_id);
+
+            let (swap_token_result, fee) = Self::calculate_swap_output(
+                &pool,
+                fee_tier,
+                constant,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
                 amount,
             )?;
 
-            T::MultiToken::safe_transfer(
-                pool.clone(),
-                pool.clone(),
-                operator.clone(),
-                corresponding_token_id,
-                swap_token_result,
+            let pool_origin_token_balance_after = pool_origin_token_balance
+                .checked_add(&amount)
+                .ok_or(Error::<T>::Overflow)?;
+            let pool_dest_token_balance_after = pool_dest_token_balance
+                .checked_sub(&swap_token_result)
+                .ok_or(Error::<T>::Overflow)?;
+            Self::ensure_price_impact(
+                &pool,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+                pool_origin_token_balance_after,
+                pool_dest_token_balance_after,
             )?;
+            Self::update_volatility(
+                &pool,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+                pool_origin_token_balance_after,
+                pool_dest_token_balance_after,
+            )?;
+            if token_id == first_asset_id {
+                Self::ensure_circuit_breaker(
+                    &pool,
+                    pool_origin_token_balance_after,
+                    pool_dest_token_balance_after,
+                )?;
+            } else {
+                Self::ensure_circuit_breaker(
+                    &pool,
+                    pool_dest_token_balance_after,
+                    pool_origin_token_balance_after,
+                )?;
+            }
+
+            Self::transfer_from_user(token_id, &operator, &pool, amount, keep_alive)?;
+
+            Self::transfer_from_pool(corresponding_token_id, &pool, &recipient, swap_token_result)?;
 
             // Since we took the fee, we need to alter the pool constant
             Self::fetch_pool_constant(&pool, &token_id, &corresponding_token_id)?;
 
             Self::deposit_event(Event::<T>::Swapped {
                 operator,
+                recipient,
                 pool_account: pool,
-                first_asset: first_asset_id,
+                first_asset: token_id,
                 first_asset_amount: amount,
                 second_asset: corresponding_token_id,
                 second_asset_amount: swap_token_result,
+                fee,
             });
 
             Ok(swap_token_result)
         }
 
+        // The recipient receives the pool share
+        // Returns the amount of corresponding tokens that has been deposited
+        // `keep_alive` is passed to `transfer_from_user` for both assets
         fn dep(
             operator: T::AccountId,
+            recipient: T::AccountId,
             pool: T::AccountId,
             token_id: T::AssetId,
             amount: T::Balance,
-        ) -> DispatchResult {
+            keep_alive: bool,
+        ) -> Result<T::Balance, DispatchError> {
             ensure!(!amount.is_zero(), Error::<T>::DepositingZeroAmount);
             ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
-            Self::check_balance(&token_id, &operator, amount)?;
+            Self::ensure_pool_unlocked(&pool)?;
+            Self::check_balance(&token_id, &operator, amount, keep_alive)?;
 
-            let (first_asset_id, second_asset_id, _) = Self::get_pool(&pool).unwrap();
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                status,
+                ..
+            } = Self::get_pool(&pool).unwrap();
+            ensure!(status == PoolStatus::Live, Error::<T>::PoolNotLive);
             let corresponding_token_id = if token_id == first_asset_id {
                 second_asset_id
             } else if token_id == second_asset_id {
@@ -482,69 +2452,62 @@ pub mod pallet {
                 first_asset_id
             };
 
-            let pool_origin_token_balance =
-                T::MultiToken::get_balance(&token_id, &pool).ok_or(Error::<T>::EmptyPool)?;
-            let pool_dest_token_balance =
-                T::MultiToken::get_balance(&corresponding_token_id, &pool)
-                    .ok_or(Error::<T>::EmptyPool)?;
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
             ensure!(
                 !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
                 Error::<T>::EmptyPool
             );
 
-            let corresponding_token_amount = amount
-                .checked_mul(&pool_dest_token_balance)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_div(&pool_origin_token_balance)
-                .ok_or(Error::<T>::Overflow)?;
+            // The operator pays the corresponding asset rounded up, the share is rounded down
+            let corresponding_token_amount = math::deposit_corresponding_amount(
+                amount,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+            )
+            .ok_or(Error::<T>::Overflow)?;
             Self::check_balance(
                 &corresponding_token_id,
                 &operator,
                 corresponding_token_amount,
+                keep_alive,
             )?;
 
             let current_full_share =
                 TotalPoolShares::<T>::get(&pool).ok_or(Error::<T>::NoSuchPool)?;
             ensure!(!current_full_share.is_zero(), Error::<T>::NoSuchPool);
-            let operator_pool_share = match PoolShares::<T>::get(&pool, &operator) {
+            let recipient_pool_share = match PoolShares::<T>::get(&pool, &recipient) {
                 Some(share) => share,
                 None => Zero::zero(),
             };
-            let add_operator_pool_share = amount
-                .checked_mul(&current_full_share)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_div(&pool_origin_token_balance)
-                .ok_or(Error::<T>::Overflow)?;
+            let add_operator_pool_share =
+                math::shares_to_mint(amount, pool_origin_token_balance, current_full_share)
+                    .ok_or(Error::<T>::Overflow)?;
             let new_full_share = current_full_share
                 .checked_add(&add_operator_pool_share)
                 .ok_or(Error::<T>::Overflow)?;
-            let new_operator_pool_share = operator_pool_share
+            let new_recipient_pool_share = recipient_pool_share
                 .checked_add(&add_operator_pool_share)
                 .ok_or(Error::<T>::Overflow)?;
 
-            T::MultiToken::safe_transfer(
-                operator.clone(),
-                operator.clone(),
-                pool.clone(),
-                token_id,
-                amount,
-            )?;
+            Self::transfer_from_user(token_id, &operator, &pool, amount, keep_alive)?;
 
-            T::MultiToken::safe_transfer(
-                operator.clone(),
-                operator.clone(),
-                pool.clone(),
+            Self::transfer_from_user(
                 corresponding_token_id,
+                &operator,
+                &pool,
                 corresponding_token_amount,
+                keep_alive,
             )?;
 
             Self::fetch_pool_constant(&pool, &token_id, &corresponding_token_id)?;
 
             TotalPoolShares::<T>::set(&pool, Some(new_full_share));
-            PoolShares::<T>::set(&pool, &operator, Some(new_operator_pool_share));
+            PoolShares::<T>::set(&pool, &recipient, Some(new_recipient_pool_share));
 
             Self::deposit_event(Event::<T>::Deposited {
                 operator,
+                recipient,
                 pool_account: pool,
                 first_asset: token_id,
                 first_asset_amount: amount,
@@ -552,20 +2515,30 @@ pub mod pallet {
                 second_asset_amount: corresponding_token_amount,
             });
 
-            Ok(())
+            Ok(corresponding_token_amount)
         }
 
         // Returns the amount of corresponding tokens that has been withdrawn
+        // Both tokens are sent to the recipient
         fn with(
             operator: T::AccountId,
+            recipient: T::AccountId,
             pool: T::AccountId,
             token_id: T::AssetId,
             amount: T::Balance,
         ) -> Result<T::Balance, DispatchError> {
             ensure!(!amount.is_zero(), Error::<T>::WithdrawingZeroAmount);
             ensure!(Self::get_pool(&pool) != None, Error::<T>::NoSuchPool);
+            Self::ensure_pool_unlocked(&pool)?;
 
-            let (first_asset_id, second_asset_id, _) = Self::get_pool(&pool).unwrap();
+            let PoolInfo {
+                first_asset: first_asset_id,
+                second_asset: second_asset_id,
+                status,
+                ..
+            } = Self::get_pool(&pool).unwrap();
+            // Liquidity providers are still able to leave a frozen pool
+            ensure!(status != PoolStatus::Destroying, Error::<T>::PoolNotLive);
             let corresponding_token_id = if token_id == first_asset_id {
                 second_asset_id
             } else if token_id == second_asset_id {
@@ -575,21 +2548,19 @@ pub mod pallet {
                 first_asset_id
             };
 
-            let pool_origin_token_balance =
-                T::MultiToken::get_balance(&token_id, &pool).ok_or(Error::<T>::EmptyPool)?;
-            let pool_dest_token_balance =
-                T::MultiToken::get_balance(&corresponding_token_id, &pool)
-                    .ok_or(Error::<T>::EmptyPool)?;
+            let pool_origin_token_balance = T::Assets::balance(token_id, &pool);
+            let pool_dest_token_balance = T::Assets::balance(corresponding_token_id, &pool);
             ensure!(
                 !pool_origin_token_balance.is_zero() && !pool_dest_token_balance.is_zero(),
                 Error::<T>::EmptyPool
             );
 
-            let corresponding_token_amount = amount
-                .checked_mul(&pool_dest_token_balance)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_div(&pool_origin_token_balance)
-                .ok_or(Error::<T>::Overflow)?;
+            let corresponding_token_amount = math::withdraw_corresponding_amount(
+                amount,
+                pool_origin_token_balance,
+                pool_dest_token_balance,
+            )
+            .ok_or(Error::<T>::Overflow)?;
 
             let current_full_share =
                 TotalPoolShares::<T>::get(&pool).ok_or(Error::<T>::NoSuchPool)?;
@@ -598,11 +2569,11 @@ pub mod pallet {
                 Some(share) => share,
                 None => Zero::zero(),
             };
-            let sub_operator_pool_share = amount
-                .checked_mul(&current_full_share)
-                .ok_or(Error::<T>::Overflow)?
-                .checked_div(&pool_origin_token_balance)
-                .ok_or(Error::<T>::Overflow)?;
+            // The operator receives the corresponding asset rounded down, the burned share is
+            // rounded up, so nobody can withdraw more than their share
+            let sub_operator_pool_share =
+                math::shares_to_burn(amount, pool_origin_token_balance, current_full_share)
+                    .ok_or(Error::<T>::Overflow)?;
             let new_full_share = current_full_share
                 .checked_sub(&sub_operator_pool_share)
                 .ok_or(Error::<T>::Overflow)?;
@@ -613,19 +2584,12 @@ pub mod pallet {
             TotalPoolShares::<T>::set(&pool, Some(new_full_share));
             PoolShares::<T>::set(&pool, &operator, Some(new_operator_pool_share));
 
-            T::MultiToken::safe_transfer(
-                pool.clone(),
-                pool.clone(),
-                operator.clone(),
-                token_id,
-                amount,
-            )?;
+            Self::transfer_from_pool(token_id, &pool, &recipient, amount)?;
 
-            T::MultiToken::safe_transfer(
-                pool.clone(),
-                pool.clone(),
-                operator.clone(),
+            Self::transfer_from_pool(
                 corresponding_token_id,
+                &pool,
+                &recipient,
                 corresponding_token_amount,
             )?;
 
@@ -633,6 +2597,7 @@ pub mod pallet {
 
             Self::deposit_event(Event::<T>::Withdrawed {
                 operator,
+                recipient,
                 pool_account: pool,
                 first_asset: token_id,
                 first_asset_amount: amount,
@@ -648,17 +2613,162 @@ pub mod pallet {
             token_id: &T::AssetId,
             corresponding_token_id: &T::AssetId,
         ) -> DispatchResult {
-            let (first_asset_id, second_asset_id, _) =
-                Self::get_pool(pool).ok_or(Error::<T>::NoSuchPool)?;
-            let pool_origin_token_balance =
-                T::MultiToken::get_balance(token_id, pool).ok_or(Error::<T>::EmptyPool)?;
-            let pool_dest_token_balance = T::MultiToken::get_balance(corresponding_token_id, pool)
-                .ok_or(Error::<T>::EmptyPool)?;
-            let new_constant = pool_origin_token_balance
-                .checked_mul(&pool_dest_token_balance)
+            let mut pool_info = Self::get_pool(pool).ok_or(Error::<T>::NoSuchPool)?;
+            let pool_origin_token_balance = T::Assets::balance(*token_id, pool);
+            let pool_dest_token_balance = T::Assets::balance(*corresponding_token_id, pool);
+            pool_info.constant = math::mul(pool_origin_token_balance, pool_dest_token_balance)
                 .ok_or(Error::<T>::Overflow)?;
-            Pools::<T>::set(pool, Some((first_asset_id, second_asset_id, new_constant)));
+            Pools::<T>::insert(pool, pool_info);
+            Ok(())
+        }
+
+        // Returns the creation deposit to the pool creator
+        fn release_creation_deposit(pool_info: &PoolInfoOf<T>) {
+            T::Currency::unreserve(&pool_info.creator, pool_info.deposit);
+        }
+
+        // Pays out the value of `shares` in an asset of a pool that is being destroyed
+        // The value is rounded down, the rounding remainder is swept as dust
+        // The pool is being emptied, so it is not kept above the minimum balance
+        // A payout that the provider can not receive, for example one below the minimum balance
+        // of an asset the provider has no account in, is skipped and stays in the pool for the
+        // remaining providers and the dust, so one provider can not block the destruction
+        fn pay_out_shares(
+            pool: &T::AccountId,
+            provider: &T::AccountId,
+            token_id: &T::AssetId,
+            shares: T::Balance,
+            total_shares: T::Balance,
+        ) -> DispatchResult {
+            let amount =
+                math::share_value(shares, T::Assets::balance(*token_id, pool), total_shares)
+                    .ok_or(Error::<T>::Overflow)?;
+            if !amount.is_zero() && Self::can_receive(token_id, provider, amount) {
+                T::Assets::transfer(*token_id, pool, provider, amount, false)?;
+            }
+            Ok(())
+        }
+
+        // Transfers everything that is left of an asset in the pool to `Config::DustReceiver`
+        // Returns the amount that has been transferred
+        // Dust that the receiver can not receive stays on the pool account
+        fn sweep_dust(
+            pool: &T::AccountId,
+            token_id: &T::AssetId,
+        ) -> Result<T::Balance, DispatchError> {
+            let dust = T::Assets::balance(*token_id, pool);
+            let dust_receiver = T::DustReceiver::get();
+            if dust.is_zero() || !Self::can_receive(token_id, &dust_receiver, dust) {
+                return Ok(Zero::zero());
+            }
+            Self::transfer_from_pool(*token_id, pool, &dust_receiver, dust)?;
+            Ok(dust)
+        }
+
+        // Whether `amount` can be deposited to `who`, it can not be for example when it is below
+        // the minimum balance of an asset that `who` has no account in
+        fn can_receive(token_id: &T::AssetId, who: &T::AccountId, amount: T::Balance) -> bool {
+            T::Assets::can_deposit(*token_id, who, amount, false) == DepositConsequence::Success
+        }
+
+        // Transfers from a user, whose own balance is kept alive unless they have allowed it to
+        // be reaped with `set_allow_death`
+        // Amounts that the pallet has paid out to the user within the same call are moved on
+        // with `keep_alive` off, the user ends up with at least the balance they had before
+        fn transfer_from_user(
+            token_id: T::AssetId,
+            source: &T::AccountId,
+            dest: &T::AccountId,
+            amount: T::Balance,
+            keep_alive: bool,
+        ) -> DispatchResult {
+            T::Assets::transfer(token_id, source, dest, amount, keep_alive)?;
             Ok(())
         }
+
+        // Transfers from a pool, which has to keep at least the minimum balance of the asset
+        // unless everything is taken, otherwise the rest would be removed as dust
+        // The pool account itself is never reaped, since the pallet is its provider
+        fn transfer_from_pool(
+            token_id: T::AssetId,
+            pool: &T::AccountId,
+            dest: &T::AccountId,
+            amount: T::Balance,
+        ) -> DispatchResult {
+            let keep_alive = amount < T::Assets::balance(token_id, pool);
+            T::Assets::transfer(token_id, pool, dest, amount, keep_alive)?;
+            Ok(())
+        }
+    }
+
+    impl<T: Config> FlashLoan<T::AccountId, T::AssetId, T::Balance> for Pallet<T> {
+        fn flash_loan(
+            borrower: &T::AccountId,
+            pool: &T::AccountId,
+            asset: T::AssetId,
+            amount: T::Balance,
+        ) -> DispatchResult {
+            // Unlike extrinsics, trait calls are not transactional by default
+            frame_support::storage::with_storage_layer(|| -> DispatchResult {
+                ensure!(!amount.is_zero(), Error::<T>::WithdrawingZeroAmount);
+                let PoolInfo {
+                    first_asset: first_asset_id,
+                    second_asset: second_asset_id,
+                    status,
+                    ..
+                } = Self::get_pool(pool).ok_or(Error::<T>::NoSuchPool)?;
+                ensure!(status == PoolStatus::Live, Error::<T>::PoolNotLive);
+                Self::ensure_pool_unlocked(pool)?;
+                let corresponding_token_id = if asset == first_asset_id {
+                    second_asset_id
+                } else if asset == second_asset_id {
+                    first_asset_id
+                } else {
+                    ensure!(false, Error::<T>::NoSuchTokenInPool);
+                    first_asset_id
+                };
+
+                let pool_token_balance = T::Assets::balance(asset, pool);
+                ensure!(amount <= pool_token_balance, Error::<T>::NotEnoughBalance);
+
+                // The fee is rounded up, so that it is never zero for a non-zero fee
+                let fee =
+                    math::mul_div_ceil(amount, T::FlashLoanFee::get(), T::HundredPercent::get())
+                        .ok_or(Error::<T>::Overflow)?;
+                let expected_balance = pool_token_balance
+                    .checked_add(&fee)
+                    .ok_or(Error::<T>::Overflow)?;
+
+                Self::transfer_from_pool(asset, pool, borrower, amount)?;
+
+                // Same as in flash swaps, the receiver can not use the pool it borrows from
+                LockedPools::<T>::insert(pool, ());
+                T::FlashLoanReceiver::on_flash_loan(borrower, pool, asset, amount, fee)?;
+                LockedPools::<T>::remove(pool);
+
+                let new_pool_token_balance = T::Assets::balance(asset, pool);
+                ensure!(
+                    new_pool_token_balance >= expected_balance,
+                    Error::<T>::FlashLoanNotRepaid
+                );
+
+                // The fee stays in the pool and goes to liquidity providers
+                Self::fetch_pool_constant(pool, &asset, &corresponding_token_id)?;
+
+                Self::deposit_event(Event::<T>::FlashLoaned {
+                    borrower: borrower.clone(),
+                    pool_account: pool.clone(),
+                    asset,
+                    borrowed: amount,
+                    repaid: new_pool_token_balance
+                        .checked_add(&amount)
+                        .ok_or(Error::<T>::Overflow)?
+                        .checked_sub(&pool_token_balance)
+                        .ok_or(Error::<T>::Overflow)?,
+                });
+
+                Ok(())
+            })
+        }
     }
 }
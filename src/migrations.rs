@@ -0,0 +1,58 @@
+// Storage migrations of the pallet, applied by `Hooks::on_runtime_upgrade`
+
+use crate::{math, AssetPools, Config, Pallet, PoolIndex, PoolInfo, PoolStatus, Pools};
+use frame_support::traits::tokens::fungibles::Inspect;
+use frame_support::traits::Get;
+use frame_support::weights::Weight;
+use sp_runtime::traits::Zero;
+
+pub mod v1 {
+    use super::*;
+
+    // A value of `Pools` before version 1: pair of assets in the pool & pool constant
+    pub type OldPoolInfoOf<T> = (
+        <T as Config>::AssetId,
+        <T as Config>::AssetId,
+        <T as Config>::Balance,
+    );
+
+    // Converts `Pools` values from the tuple into `PoolInfo`
+    // Old pools have no creation deposit, so the pool account becomes the creator and nothing
+    // is reserved. They are live, use the default fee tier and get the pool constant
+    // recomputed in 256 bits from their balances
+    // The pair index and the asset graph are filled in, an existing pool of the same pair and
+    // fee tier keeps its index entry
+    // Pools created before version 1 have no provider from the pallet, so one is added
+    pub fn migrate<T: Config>() -> Weight {
+        let fee_tier = Pallet::<T>::default_fee_tier();
+        let mut pools = 0;
+        Pools::<T>::translate::<OldPoolInfoOf<T>, _>(|pool, (first_asset, second_asset, _)| {
+            pools += 1;
+            let constant = math::mul(
+                T::Assets::balance(first_asset, &pool),
+                T::Assets::balance(second_asset, &pool),
+            )
+            .unwrap_or_default();
+
+            let pair = Pallet::<T>::pair_key(first_asset, second_asset);
+            if !PoolIndex::<T>::contains_key(&pair, &fee_tier) {
+                PoolIndex::<T>::insert(&pair, &fee_tier, &pool);
+            }
+            AssetPools::<T>::insert(&first_asset, &pool, ());
+            AssetPools::<T>::insert(&second_asset, &pool, ());
+            frame_system::Pallet::<T>::inc_providers(&pool);
+
+            Some(PoolInfo {
+                first_asset,
+                second_asset,
+                constant,
+                creator: pool,
+                deposit: Zero::zero(),
+                status: PoolStatus::Live,
+                fee_tier,
+            })
+        });
+
+        T::DbWeight::get().reads_writes(5 * pools, 5 * pools)
+    }
+}
@@ -1,11 +1,17 @@
 use crate as pallet_dex;
-use frame_support::traits::{ConstU128, ConstU16, ConstU64};
+use crate::adapters::MultiTokenAdapter;
+use crate::traits::{OnFlashLoan, OnFlashSwap};
+use frame_support::parameter_types;
+use frame_support::traits::{ConstU128, ConstU16, ConstU32, ConstU64};
 use frame_system as system;
+use pallet_multi_token::multi_token::MultiTokenTrait;
 use sp_core::H256;
 use sp_runtime::{
     testing::Header,
     traits::{BlakeTwo256, IdentityLookup},
 };
+use sp_runtime::{DispatchResult, Permill};
+use std::cell::RefCell;
 
 type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
 type Block = frame_system::mocking::MockBlock<Test>;
@@ -18,6 +24,7 @@ frame_support::construct_runtime!(
         UncheckedExtrinsic = UncheckedExtrinsic,
     {
         System: frame_system,
+        Balances: pallet_balances,
         MultiTokenPallet: pallet_multi_token,
         Dex: pallet_dex,
     }
@@ -41,13 +48,25 @@ impl system::Config for Test {
     type BlockHashCount = ConstU64<250>;
     type Version = ();
     type PalletInfo = PalletInfo;
-    type AccountData = ();
+    type AccountData = pallet_balances::AccountData<u128>;
     type OnNewAccount = ();
     type OnKilledAccount = ();
     type SystemWeightInfo = ();
     type SS58Prefix = ConstU16<42>;
     type OnSetCode = ();
-    type MaxConsumers = frame_support::traits::ConstU32<16>;
+    type MaxConsumers = ConstU32<16>;
+}
+
+impl pallet_balances::Config for Test {
+    type MaxLocks = ();
+    type MaxReserves = ();
+    type ReserveIdentifier = [u8; 8];
+    type Balance = u128;
+    type Event = Event;
+    type DustRemoval = ();
+    type ExistentialDeposit = ConstU128<1>;
+    type AccountStore = System;
+    type WeightInfo = ();
 }
 
 impl pallet_multi_token::Config for Test {
@@ -56,20 +75,91 @@ impl pallet_multi_token::Config for Test {
     type Balance = u128;
 }
 
+thread_local! {
+    // An amount of the other asset that the flash swap handler sends to the pool
+    pub static FLASH_SWAP_REPAYMENT: RefCell<u128> = RefCell::new(0);
+    // Whether the flash swap handler deposits into the pool before repaying it
+    pub static FLASH_SWAP_REENTER: RefCell<bool> = RefCell::new(false);
+    // An amount of the borrowed asset that the flash loan receiver sends back to the pool
+    pub static FLASH_LOAN_REPAYMENT: RefCell<u128> = RefCell::new(0);
+    // Whether the flash loan receiver swaps the borrowed asset in the pool before repaying it
+    pub static FLASH_LOAN_REENTER: RefCell<bool> = RefCell::new(false);
+}
+
+pub struct FlashSwapRepayer;
+impl OnFlashSwap<u64, u64, u128> for FlashSwapRepayer {
+    fn on_flash_swap(
+        operator: &u64,
+        pool: &u64,
+        _asset_out: u64,
+        _amount_out: u128,
+        asset_in: u64,
+    ) -> DispatchResult {
+        if FLASH_SWAP_REENTER.with(|v| *v.borrow()) {
+            Dex::deposit(Origin::signed(*operator), *pool, asset_in, 10, None)?;
+        }
+        let repayment = FLASH_SWAP_REPAYMENT.with(|v| *v.borrow());
+        MultiTokenPallet::safe_transfer(*operator, *operator, *pool, asset_in, repayment)?;
+        Ok(())
+    }
+}
+
+pub struct FlashLoanRepayer;
+impl OnFlashLoan<u64, u64, u128> for FlashLoanRepayer {
+    fn on_flash_loan(
+        borrower: &u64,
+        pool: &u64,
+        asset: u64,
+        _amount: u128,
+        _fee: u128,
+    ) -> DispatchResult {
+        if FLASH_LOAN_REENTER.with(|v| *v.borrow()) {
+            Dex::swap_token(Origin::signed(*borrower), *pool, asset, 10, *borrower, None)?;
+        }
+        let repayment = FLASH_LOAN_REPAYMENT.with(|v| *v.borrow());
+        MultiTokenPallet::safe_transfer(*borrower, *borrower, *pool, asset, repayment)?;
+        Ok(())
+    }
+}
+
+parameter_types! {
+    pub const VolatilitySmoothing: Permill = Permill::from_percent(50);
+}
+
 impl pallet_dex::Config for Test {
     type Event = Event;
     type AssetId = u64;
     type Balance = u128;
     type DefaultShare = ConstU128<10000>;
-    type MultiToken = MultiTokenPallet;
+    type Assets = MultiTokenAdapter<Test, MultiTokenPallet>;
     type HundredPercentMinusFee = ConstU128<997>;
     type HundredPercent = ConstU128<1000>;
+    type VolatilitySmoothing = VolatilitySmoothing;
+    type Currency = Balances;
+    type PoolCreationDeposit = ConstU128<10>;
+    type CreatePoolOrigin = frame_system::EnsureSigned<u64>;
+    type AdminOrigin = frame_system::EnsureRoot<u64>;
+    type DustReceiver = ConstU64<999>;
+    type DestroyBatchSize = ConstU32<1>;
+    type MaxHops = ConstU32<2>;
+    type MaxRouteCandidates = ConstU32<16>;
+    type MaxSplitRoutes = ConstU32<2>;
+    type MaxBatchOperations = ConstU32<4>;
+    type WeightInfo = ();
+    type FlashSwapHandler = FlashSwapRepayer;
+    type FlashLoanReceiver = FlashLoanRepayer;
+    type FlashLoanFee = ConstU128<9>;
 }
 
 // Build genesis storage according to the mock runtime.
 pub fn new_test_ext() -> sp_io::TestExternalities {
-    system::GenesisConfig::default()
+    let mut storage = system::GenesisConfig::default()
         .build_storage::<Test>()
-        .unwrap()
-        .into()
+        .unwrap();
+    pallet_balances::GenesisConfig::<Test> {
+        balances: vec![(1, 100), (2, 100)],
+    }
+    .assimilate_storage(&mut storage)
+    .unwrap();
+    storage.into()
 }
@@ -0,0 +1,121 @@
+// A mock runtime that trades assets of `pallet_assets` instead of `pallet_multi_token`,
+// together with the native currency of `pallet_balances`
+use crate as pallet_dex;
+use crate::{adapters::NativeOrAssetAdapter, NativeOrAsset};
+use frame_support::traits::{ConstU128, ConstU16, ConstU32, ConstU64};
+use frame_system as system;
+use sp_core::H256;
+use sp_runtime::{
+    testing::Header,
+    traits::{BlakeTwo256, IdentityLookup},
+};
+
+type UncheckedExtrinsic = frame_system::mocking::MockUncheckedExtrinsic<Test>;
+type Block = frame_system::mocking::MockBlock<Test>;
+
+frame_support::construct_runtime!(
+    pub enum Test where
+        Block = Block,
+        NodeBlock = Block,
+        UncheckedExtrinsic = UncheckedExtrinsic,
+    {
+        System: frame_system,
+        Balances: pallet_balances,
+        Assets: pallet_assets,
+        Dex: pallet_dex,
+    }
+);
+
+impl system::Config for Test {
+    type BaseCallFilter = frame_support::traits::Everything;
+    type BlockWeights = ();
+    type BlockLength = ();
+    type DbWeight = ();
+    type Origin = Origin;
+    type Call = Call;
+    type Index = u64;
+    type BlockNumber = u64;
+    type Hash = H256;
+    type Hashing = BlakeTwo256;
+    type AccountId = u64;
+    type Lookup = IdentityLookup<Self::AccountId>;
+    type Header = Header;
+    type Event = Event;
+    type BlockHashCount = ConstU64<250>;
+    type Version = ();
+    type PalletInfo = PalletInfo;
+    type AccountData = pallet_balances::AccountData<u128>;
+    type OnNewAccount = ();
+    type OnKilledAccount = ();
+    type SystemWeightInfo = ();
+    type SS58Prefix = ConstU16<42>;
+    type OnSetCode = ();
+    type MaxConsumers = ConstU32<16>;
+}
+
+impl pallet_balances::Config for Test {
+    type MaxLocks = ();
+    type MaxReserves = ();
+    type ReserveIdentifier = [u8; 8];
+    type Balance = u128;
+    type Event = Event;
+    type DustRemoval = ();
+    type ExistentialDeposit = ConstU128<1>;
+    type AccountStore = System;
+    type WeightInfo = ();
+}
+
+impl pallet_assets::Config for Test {
+    type Event = Event;
+    type Balance = u128;
+    type AssetId = u64;
+    type Currency = Balances;
+    type ForceOrigin = frame_system::EnsureRoot<u64>;
+    type AssetDeposit = ConstU128<0>;
+    type AssetAccountDeposit = ConstU128<0>;
+    type MetadataDepositBase = ConstU128<0>;
+    type MetadataDepositPerByte = ConstU128<0>;
+    type ApprovalDeposit = ConstU128<0>;
+    type StringLimit = ConstU32<50>;
+    type Freezer = ();
+    type Extra = ();
+    type WeightInfo = ();
+}
+
+impl pallet_dex::Config for Test {
+    type Event = Event;
+    type AssetId = NativeOrAsset<u64>;
+    type Balance = u128;
+    type DefaultShare = ConstU128<10000>;
+    type Assets = NativeOrAssetAdapter<Test, Assets>;
+    type HundredPercentMinusFee = ConstU128<997>;
+    type HundredPercent = ConstU128<1000>;
+    type VolatilitySmoothing = ();
+    type Currency = Balances;
+    type PoolCreationDeposit = ConstU128<10>;
+    type CreatePoolOrigin = frame_system::EnsureSigned<u64>;
+    type AdminOrigin = frame_system::EnsureRoot<u64>;
+    type DustReceiver = ConstU64<999>;
+    type DestroyBatchSize = ConstU32<1>;
+    type MaxHops = ConstU32<2>;
+    type MaxRouteCandidates = ConstU32<16>;
+    type MaxSplitRoutes = ConstU32<2>;
+    type MaxBatchOperations = ConstU32<4>;
+    type WeightInfo = ();
+    type FlashSwapHandler = ();
+    type FlashLoanReceiver = ();
+    type FlashLoanFee = ConstU128<9>;
+}
+
+// Build genesis storage according to the mock runtime.
+pub fn new_test_ext() -> sp_io::TestExternalities {
+    let mut storage = system::GenesisConfig::default()
+        .build_storage::<Test>()
+        .unwrap();
+    pallet_balances::GenesisConfig::<Test> {
+        balances: vec![(1, 100), (2, 100)],
+    }
+    .assimilate_storage(&mut storage)
+    .unwrap();
+    storage.into()
+}
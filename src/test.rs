@@ -1,8 +1,21 @@
-use crate::{mock::*, Error};
+use crate::{
+    fuzzing::{self, Operation},
+    mock::*,
+    traits::FlashLoan,
+    Error, LiquidityOperation, PoolInfo, PoolStatus, WeightInfo,
+};
 
 use frame_benchmarking::frame_support::assert_noop;
-use frame_support::assert_ok;
+use frame_support::{
+    assert_ok,
+    storage::unhashed,
+    traits::{ConstU32, GenesisBuild, GetStorageVersion, Hooks, StorageVersion},
+    BoundedVec,
+};
 use pallet_multi_token::multi_token::MultiTokenTrait;
+use proptest::prelude::*;
+use sp_core::U256;
+use sp_runtime::{traits::Zero, Permill};
 
 #[test]
 fn init_pool() {
@@ -11,9 +24,25 @@ fn init_pool() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
 
-        assert_eq!(Dex::get_pool(314159265), Some((0, 1, 2500)));
+        let pool_info = Dex::get_pool(314159265).unwrap();
+        assert_eq!(
+            (
+                pool_info.first_asset,
+                pool_info.second_asset,
+                pool_info.constant
+            ),
+            (0, 1, U256::from(2500))
+        );
         assert_eq!(Dex::get_pool_share(314159265, 1), Some(10000));
     });
 }
@@ -26,12 +55,69 @@ fn swap_tokens() {
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
         assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
-        assert_ok!(Dex::swap_token(Origin::signed(2), 314159265, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            10,
+            2,
+            None
+        ));
 
         assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(0));
-        // One token was used as slippage, another one as fee
-        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(8));
+        // 2500 / 60 is rounded up to 42 in favour of the pool, so 8 tokens are swapped
+        // and one of them is taken as fee
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(7));
+    });
+}
+
+#[test]
+fn swapped_event_follows_swap_direction() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            1,
+            10,
+            2,
+            None
+        ));
+
+        // `first_asset` is the input of the swap, not the first asset of the pool
+        System::assert_last_event(Event::Dex(crate::Event::Swapped {
+            operator: 2,
+            recipient: 2,
+            pool_account: 314159265,
+            first_asset: 1,
+            first_asset_amount: 10,
+            second_asset: 0,
+            second_asset_amount: 7,
+            fee: Permill::from_parts(3000),
+        }));
     });
 }
 
@@ -44,8 +130,16 @@ fn depositing_liquidity() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
         assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
         assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 10));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
-        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10, None));
 
         assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(0));
         assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(0));
@@ -61,15 +155,23 @@ fn withdrawing_liquidity() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
-        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 10, 1, None));
 
         assert_eq!(MultiTokenPallet::get_balance(&0, &1), Some(60));
         assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(60));
         assert_eq!(Dex::get_pool_share(314159265, 1), Some(8000));
         assert_eq!(Dex::get_total_pool_shares(314159265), Some(8000));
 
-        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 40));
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 40, 1, None));
 
         assert_eq!(MultiTokenPallet::get_balance(&0, &1), Some(100));
         assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(100));
@@ -84,7 +186,15 @@ fn init_pool_with_same_assets() {
     new_test_ext().execute_with(|| {
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 0, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            0,
+            50,
+            Dex::default_fee_tier()
+        ));
     });
 }
 
@@ -110,28 +220,44 @@ fn abuse_without_tokens() {
             10000
         ));
         assert_noop!(
-            Dex::init(Origin::signed(1), 314159265, 0, 500, 1, 500),
+            Dex::init(
+                Origin::signed(1),
+                314159265,
+                0,
+                500,
+                1,
+                500,
+                Dex::default_fee_tier()
+            ),
             Error::<Test>::NotEnoughBalance
         );
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
         assert_noop!(
-            Dex::swap_token(Origin::signed(1), 314159265, 1, 500),
+            Dex::swap_token(Origin::signed(1), 314159265, 1, 500, 1, None),
             Error::<Test>::NotEnoughBalance
         );
         assert_noop!(
-            Dex::deposit(Origin::signed(1), 314159265, 1, 500),
+            Dex::deposit(Origin::signed(1), 314159265, 1, 500, None),
             Error::<Test>::NotEnoughBalance
         );
-        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10000));
+        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10000, None));
         assert_noop!(
-            Dex::withdraw(Origin::signed(1), 314159265, 1, 500),
+            Dex::withdraw(Origin::signed(1), 314159265, 1, 500, 1, None),
             Error::<Test>::Overflow
         );
         assert_noop!(
-            Dex::withdraw(Origin::signed(1), 314159265, 1, 51),
+            Dex::withdraw(Origin::signed(1), 314159265, 1, 51, 1, None),
             Error::<Test>::Overflow
         );
-        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 1, 50));
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 1, 50, 1, None));
     });
 }
 
@@ -139,7 +265,7 @@ fn abuse_without_tokens() {
 fn using_uninitialized_pool() {
     new_test_ext().execute_with(|| {
         assert_noop!(
-            Dex::swap_token(Origin::signed(2), 314159265, 0, 10),
+            Dex::swap_token(Origin::signed(2), 314159265, 0, 10, 2, None),
             Error::<Test>::NoSuchPool
         );
     });
@@ -153,20 +279,36 @@ fn zero_amounts() {
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
         assert_noop!(
-            Dex::init(Origin::signed(1), 314159265, 0, 0, 1, 50),
+            Dex::init(
+                Origin::signed(1),
+                314159265,
+                0,
+                0,
+                1,
+                50,
+                Dex::default_fee_tier()
+            ),
             Error::<Test>::DepositingZeroAmount
         );
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
         assert_noop!(
-            Dex::swap_token(Origin::signed(1), 314159265, 1, 0),
+            Dex::swap_token(Origin::signed(1), 314159265, 1, 0, 1, None),
             Error::<Test>::DepositingZeroAmount
         );
         assert_noop!(
-            Dex::deposit(Origin::signed(1), 314159265, 1, 0),
+            Dex::deposit(Origin::signed(1), 314159265, 1, 0, None),
             Error::<Test>::DepositingZeroAmount
         );
         assert_noop!(
-            Dex::withdraw(Origin::signed(1), 314159265, 1, 0),
+            Dex::withdraw(Origin::signed(1), 314159265, 1, 0, 1, None),
             Error::<Test>::WithdrawingZeroAmount
         );
     });
@@ -179,9 +321,25 @@ fn creating_existing_pool() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
         assert_noop!(
-            Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50),
+            Dex::init(
+                Origin::signed(1),
+                314159265,
+                0,
+                50,
+                1,
+                50,
+                Dex::default_fee_tier()
+            ),
             Error::<Test>::PoolAlreadyExists
         );
     });
@@ -196,17 +354,25 @@ fn depositing_token_that_is_not_in_pool() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 2, 100));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
         assert_noop!(
-            Dex::swap_token(Origin::signed(1), 314159265, 2, 50),
+            Dex::swap_token(Origin::signed(1), 314159265, 2, 50, 1, None),
             Error::<Test>::NoSuchTokenInPool
         );
         assert_noop!(
-            Dex::deposit(Origin::signed(1), 314159265, 2, 50),
+            Dex::deposit(Origin::signed(1), 314159265, 2, 50, None),
             Error::<Test>::NoSuchTokenInPool
         );
         assert_noop!(
-            Dex::withdraw(Origin::signed(1), 314159265, 2, 50),
+            Dex::withdraw(Origin::signed(1), 314159265, 2, 50, 1, None),
             Error::<Test>::NoSuchTokenInPool
         );
     });
@@ -219,10 +385,18 @@ fn depositing_assets_into_dead_pool() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
-        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 50, 1, None));
         assert_noop!(
-            Dex::deposit(Origin::signed(1), 314159265, 0, 50),
+            Dex::deposit(Origin::signed(1), 314159265, 0, 50, None),
             Error::<Test>::EmptyPool
         );
     });
@@ -237,10 +411,18 @@ fn withdrawing_more_liquidity_than_in_the_pool() {
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 1000));
         assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 900));
         assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 900));
-        assert_ok!(Dex::init(Origin::signed(1), 314159265, 0, 50, 1, 50));
-        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 900));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 900, None));
         assert_noop!(
-            Dex::withdraw(Origin::signed(1), 314159265, 0, 500),
+            Dex::withdraw(Origin::signed(1), 314159265, 0, 500, 1, None),
             Error::<Test>::Overflow
         );
     });
@@ -249,6 +431,7 @@ fn withdrawing_more_liquidity_than_in_the_pool() {
 #[test]
 fn deposit_one_asset() {
     new_test_ext().execute_with(|| {
+        System::set_block_number(1);
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
         assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100000000));
         assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
@@ -273,21 +456,37 @@ fn deposit_one_asset() {
             0,
             50000,
             1,
-            50000000
+            50000000,
+            Dex::default_fee_tier()
         ));
+        assert_noop!(
+            Dex::deposit_one_asset(Origin::signed(2), 314159265, 0, 10000000, 128996, 2, None),
+            Error::<Test>::SlippageExceeded
+        );
         assert_ok!(Dex::deposit_one_asset(
             Origin::signed(2),
             314159265,
             0,
-            10000000
+            10000000,
+            128995,
+            2,
+            None
         ));
 
-        // Note, even though the balance should be 0, it is not because there is a swap fee
-        // This amount would become negligible as swap fee aproaches 0
-        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(382199));
+        // The swap takes the fee into account, so only rounding leftover remains
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(25));
         assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(10000000));
-        println!("{}", MultiTokenPallet::get_balance(&0, &314159265).unwrap());
-        println!("{}", MultiTokenPallet::get_balance(&1, &314159265).unwrap());
+        assert_eq!(Dex::get_pool_share(314159265, 2), Some(128995));
+        System::assert_last_event(Event::Dex(crate::Event::DepositedOneAsset {
+            operator: 2,
+            recipient: 2,
+            pool_account: 314159265,
+            asset: 0,
+            amount: 10000000,
+            shares: 128995,
+            refund_asset: 0,
+            refund: 25,
+        }));
     });
 }
 
@@ -318,23 +517,2330 @@ fn withdrawing_one_asset() {
             0,
             50000000,
             1,
-            50000000
+            50000000,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::deposit(
+            Origin::signed(2),
+            314159265,
+            0,
+            10000000,
+            None
         ));
-        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10000000));
         assert_ok!(Dex::withdraw_one_asset(
             Origin::signed(2),
             314159265,
             0,
-            1000000
+            1000000,
+            2,
+            None
         ));
 
-        println!("{}", MultiTokenPallet::get_balance(&0, &2).unwrap());
-        println!("{}", MultiTokenPallet::get_balance(&1, &2).unwrap());
-
         // Note, even though we made a request of withdrawal for 1000000, we receive 0.15% less
         // This happens because of the swap fee
         // As swap fee aproaches 0, the balance would aproach requested amonut
-        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(998507));
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(998506));
         assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(0));
     });
 }
+
+#[test]
+fn init_pool_with_unlisted_asset() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::set_allow_list_enabled(Origin::root(), true));
+        assert_ok!(Dex::list_asset(Origin::root(), 0));
+        assert_noop!(
+            Dex::init(
+                Origin::signed(1),
+                314159265,
+                0,
+                50,
+                1,
+                50,
+                Dex::default_fee_tier()
+            ),
+            Error::<Test>::AssetNotAllowed
+        );
+        assert_ok!(Dex::list_asset(Origin::root(), 1));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        let pool_info = Dex::get_pool(314159265).unwrap();
+        assert_eq!(
+            (
+                pool_info.first_asset,
+                pool_info.second_asset,
+                pool_info.constant
+            ),
+            (0, 1, U256::from(2500))
+        );
+    });
+}
+
+#[test]
+fn managing_allow_list() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_noop!(
+            Dex::list_asset(Origin::signed(1), 0),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_ok!(Dex::list_asset(Origin::root(), 0));
+        System::assert_last_event(Event::Dex(crate::Event::AssetListed { asset: 0 }));
+        assert_noop!(
+            Dex::list_asset(Origin::root(), 0),
+            Error::<Test>::AssetAlreadyListed
+        );
+        assert_ok!(Dex::delist_asset(Origin::root(), 0));
+        System::assert_last_event(Event::Dex(crate::Event::AssetDelisted { asset: 0 }));
+        assert_noop!(
+            Dex::delist_asset(Origin::root(), 0),
+            Error::<Test>::AssetNotListed
+        );
+        assert_eq!(Dex::get_allowed_asset(0), None);
+    });
+}
+
+#[test]
+fn pool_creation_deposit_lifecycle() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        let pool_info = Dex::get_pool(314159265).unwrap();
+        assert_eq!(pool_info.creator, 1);
+        assert_eq!(pool_info.deposit, 10);
+        assert_eq!(Balances::reserved_balance(1), 10);
+        assert_eq!(Balances::free_balance(1), 90);
+
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 50, 1, None));
+        assert_ok!(Dex::destroy_pool(Origin::signed(2), 314159265));
+        assert_eq!(Balances::reserved_balance(1), 0);
+        assert_eq!(Balances::free_balance(1), 100);
+    });
+}
+
+#[test]
+fn init_pool_without_deposit() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(3)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(3), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(3)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(3), 1, 100));
+        assert_noop!(
+            Dex::init(
+                Origin::signed(3),
+                314159265,
+                0,
+                50,
+                1,
+                50,
+                Dex::default_fee_tier()
+            ),
+            Error::<Test>::NotEnoughBalanceForDeposit
+        );
+    });
+}
+
+#[test]
+fn destroying_empty_pool() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        // The pallet keeps the pool account alive until the pool is destroyed
+        assert_eq!(System::providers(&314159265), 1);
+        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10, None));
+        assert_noop!(
+            Dex::destroy_pool(Origin::signed(2), 314159265),
+            Error::<Test>::PoolNotDestroyable
+        );
+
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 50, 1, None));
+        assert_ok!(Dex::withdraw(Origin::signed(2), 314159265, 0, 10, 2, None));
+        assert_eq!(Dex::get_total_pool_shares(314159265), Some(0));
+
+        System::set_block_number(1);
+        // `DestroyBatchSize` is 1 in the mock, so it takes a call per liquidity provider
+        assert_ok!(Dex::destroy_pool(Origin::signed(3), 314159265));
+        assert_eq!(
+            Dex::get_pool(314159265).unwrap().status,
+            PoolStatus::Destroying
+        );
+        assert_noop!(
+            Dex::swap_token(Origin::signed(2), 314159265, 0, 10, 2, None),
+            Error::<Test>::PoolNotLive
+        );
+        assert_ok!(Dex::destroy_pool(Origin::signed(3), 314159265));
+
+        assert_eq!(Dex::get_pool(314159265), None);
+        assert_eq!(Dex::get_total_pool_shares(314159265), None);
+        assert_eq!(Dex::get_pool_share(314159265, 1), None);
+        assert_eq!(Dex::get_pool_share(314159265, 2), None);
+        assert_eq!(Balances::reserved_balance(1), 0);
+        assert_eq!(System::providers(&314159265), 0);
+        System::assert_last_event(Event::Dex(crate::Event::PoolDestroyed {
+            pool_account: 314159265,
+            first_asset: 0,
+            first_asset_dust: 0,
+            second_asset: 1,
+            second_asset_dust: 0,
+        }));
+    });
+}
+
+#[test]
+fn destroying_frozen_pool() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10, None));
+        assert_noop!(
+            Dex::destroy_pool(Origin::root(), 314159265),
+            Error::<Test>::PoolNotDestroyable
+        );
+
+        assert_ok!(Dex::freeze_pool(Origin::root(), 314159265));
+        assert_noop!(
+            Dex::deposit(Origin::signed(1), 314159265, 0, 10, None),
+            Error::<Test>::PoolNotLive
+        );
+        assert_noop!(
+            Dex::destroy_pool(Origin::signed(1), 314159265),
+            Error::<Test>::PoolNotDestroyable
+        );
+        // `DestroyBatchSize` is 1 in the mock, each call pays out one liquidity provider
+        assert_ok!(Dex::destroy_pool(Origin::root(), 314159265));
+        assert_eq!(
+            Dex::get_pool(314159265).unwrap().status,
+            PoolStatus::Destroying
+        );
+        assert_ok!(Dex::destroy_pool(Origin::root(), 314159265));
+
+        assert_eq!(Dex::get_pool(314159265), None);
+        assert_eq!(MultiTokenPallet::get_balance(&0, &1), Some(90));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(90));
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(10));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(10));
+        assert_eq!(Balances::reserved_balance(1), 0);
+        System::assert_last_event(Event::Dex(crate::Event::PoolDestroyed {
+            pool_account: 314159265,
+            first_asset: 0,
+            first_asset_dust: 0,
+            second_asset: 1,
+            second_asset_dust: 0,
+        }));
+    });
+}
+
+#[test]
+fn flash_swap() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 20));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        // 10 tokens with the fee are 11 tokens, 2500 / (50 - 11) rounds up to 65
+        FLASH_SWAP_REPAYMENT.with(|v| *v.borrow_mut() = 14);
+        assert_noop!(
+            Dex::flash_swap(Origin::signed(2), 314159265, 1, 10),
+            Error::<Test>::FlashSwapNotRepaid
+        );
+
+        FLASH_SWAP_REPAYMENT.with(|v| *v.borrow_mut() = 15);
+        assert_ok!(Dex::flash_swap(Origin::signed(2), 314159265, 1, 10));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(5));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(10));
+        assert_eq!(Dex::get_pool(314159265).unwrap().constant, U256::from(2600));
+        System::assert_last_event(Event::Dex(crate::Event::FlashSwapped {
+            operator: 2,
+            pool_account: 314159265,
+            asset_out: 1,
+            amount_out: 10,
+            asset_in: 0,
+            amount_in: 15,
+        }));
+    });
+}
+
+#[test]
+fn flash_swap_price_impact() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 20));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::set_dynamic_fee(
+            Origin::root(),
+            314159265,
+            Some((Permill::from_percent(1), Permill::from_percent(5)))
+        ));
+
+        // 10 of asset 1 are repaid with 15 of asset 0, the price drops by ~38.5%
+        FLASH_SWAP_REPAYMENT.with(|v| *v.borrow_mut() = 15);
+        assert_ok!(Dex::set_default_max_price_impact(
+            Origin::root(),
+            Some(Permill::from_percent(30))
+        ));
+        assert_noop!(
+            Dex::flash_swap(Origin::signed(2), 314159265, 1, 10),
+            Error::<Test>::PriceImpactTooHigh
+        );
+
+        assert_ok!(Dex::set_default_max_price_impact(
+            Origin::root(),
+            Some(Permill::from_percent(40))
+        ));
+        assert_ok!(Dex::flash_swap(Origin::signed(2), 314159265, 1, 10));
+        // The flash swap feeds the volatility of the dynamic fee like a swap
+        assert!(!Dex::get_dynamic_fee(314159265)
+            .unwrap()
+            .volatility
+            .is_zero());
+    });
+}
+
+#[test]
+fn flash_swap_reentering_the_pool() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 40));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        // The handler deposits into the drained pool before repaying it
+        FLASH_SWAP_REENTER.with(|v| *v.borrow_mut() = true);
+        FLASH_SWAP_REPAYMENT.with(|v| *v.borrow_mut() = 15);
+        assert_noop!(
+            Dex::flash_swap(Origin::signed(2), 314159265, 1, 10),
+            Error::<Test>::PoolLocked
+        );
+
+        FLASH_SWAP_REENTER.with(|v| *v.borrow_mut() = false);
+        assert_ok!(Dex::flash_swap(Origin::signed(2), 314159265, 1, 10));
+        assert_eq!(crate::LockedPools::<Test>::get(314159265), None);
+        assert_ok!(Dex::deposit(Origin::signed(2), 314159265, 0, 10, None));
+    });
+}
+
+#[test]
+fn flash_swap_more_than_in_the_pool() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_noop!(
+            Dex::flash_swap(Origin::signed(2), 314159265, 1, 50),
+            Error::<Test>::NotEnoughBalance
+        );
+    });
+}
+
+#[test]
+fn flash_loan() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        // The fee is 0.9% of 20 rounded up
+        FLASH_LOAN_REPAYMENT.with(|v| *v.borrow_mut() = 20);
+        assert_noop!(
+            <Dex as FlashLoan<_, _, _>>::flash_loan(&2, &314159265, 0, 20),
+            Error::<Test>::FlashLoanNotRepaid
+        );
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(10));
+
+        FLASH_LOAN_REPAYMENT.with(|v| *v.borrow_mut() = 21);
+        assert_ok!(<Dex as FlashLoan<_, _, _>>::flash_loan(
+            &2, &314159265, 0, 20
+        ));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(9));
+        assert_eq!(MultiTokenPallet::get_balance(&0, &314159265), Some(51));
+        assert_eq!(Dex::get_pool(314159265).unwrap().constant, U256::from(2550));
+        System::assert_last_event(Event::Dex(crate::Event::FlashLoaned {
+            borrower: 2,
+            pool_account: 314159265,
+            asset: 0,
+            borrowed: 20,
+            repaid: 21,
+        }));
+    });
+}
+
+#[test]
+fn flash_loan_reentering_the_pool() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        // The receiver swaps the borrowed tokens in the pool before repaying the loan
+        FLASH_LOAN_REENTER.with(|v| *v.borrow_mut() = true);
+        FLASH_LOAN_REPAYMENT.with(|v| *v.borrow_mut() = 21);
+        assert_noop!(
+            <Dex as FlashLoan<_, _, _>>::flash_loan(&2, &314159265, 0, 20),
+            Error::<Test>::PoolLocked
+        );
+
+        FLASH_LOAN_REENTER.with(|v| *v.borrow_mut() = false);
+        assert_ok!(<Dex as FlashLoan<_, _, _>>::flash_loan(
+            &2, &314159265, 0, 20
+        ));
+        assert_eq!(crate::LockedPools::<Test>::get(314159265), None);
+    });
+}
+
+#[test]
+fn trading_after_deadline() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(10);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_noop!(
+            Dex::swap_token(Origin::signed(1), 314159265, 0, 10, 1, Some(9)),
+            Error::<Test>::DeadlinePassed
+        );
+        assert_noop!(
+            Dex::deposit(Origin::signed(1), 314159265, 0, 10, Some(9)),
+            Error::<Test>::DeadlinePassed
+        );
+        assert_noop!(
+            Dex::withdraw(Origin::signed(1), 314159265, 0, 10, 1, Some(9)),
+            Error::<Test>::DeadlinePassed
+        );
+        assert_noop!(
+            Dex::deposit_one_asset(Origin::signed(1), 314159265, 0, 10, 0, 1, Some(9)),
+            Error::<Test>::DeadlinePassed
+        );
+        assert_noop!(
+            Dex::withdraw_one_asset(Origin::signed(1), 314159265, 0, 10, 1, Some(9)),
+            Error::<Test>::DeadlinePassed
+        );
+        assert_ok!(Dex::swap_token(
+            Origin::signed(1),
+            314159265,
+            0,
+            10,
+            1,
+            Some(10)
+        ));
+    });
+}
+
+#[test]
+fn swapping_to_recipient() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            10,
+            3,
+            None
+        ));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(0));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(0));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &3), Some(7));
+        System::assert_last_event(Event::Dex(crate::Event::Swapped {
+            operator: 2,
+            recipient: 3,
+            pool_account: 314159265,
+            first_asset: 0,
+            first_asset_amount: 10,
+            second_asset: 1,
+            second_asset_amount: 7,
+            fee: Permill::from_parts(3000),
+        }));
+    });
+}
+
+#[test]
+fn withdrawing_to_recipient() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100000000));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100000000));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50000000,
+            1,
+            50000000,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::withdraw(Origin::signed(1), 314159265, 0, 10, 3, None));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &3), Some(10));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &3), Some(10));
+        assert_eq!(Dex::get_pool_share(314159265, 3), None);
+        System::assert_last_event(Event::Dex(crate::Event::Withdrawed {
+            operator: 1,
+            recipient: 3,
+            pool_account: 314159265,
+            first_asset: 0,
+            first_asset_amount: 10,
+            second_asset: 1,
+            second_asset_amount: 10,
+        }));
+
+        assert_ok!(Dex::withdraw_one_asset(
+            Origin::signed(1),
+            314159265,
+            0,
+            1000000,
+            4,
+            None
+        ));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &4).unwrap_or_default(), 0);
+        assert!(MultiTokenPallet::get_balance(&0, &4).unwrap() > 0);
+        assert_eq!(MultiTokenPallet::get_balance(&0, &1), Some(50000000));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(50000000));
+    });
+}
+
+#[test]
+fn depositing_one_asset_for_recipient() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100000000));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100000000));
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(1),
+            1,
+            2,
+            0,
+            10000000
+        ));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50000000,
+            1,
+            50000000,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::deposit_one_asset(
+            Origin::signed(2),
+            314159265,
+            0,
+            10000000,
+            0,
+            3,
+            None
+        ));
+
+        assert_eq!(Dex::get_pool_share(314159265, 2), None);
+        assert!(Dex::get_pool_share(314159265, 3).unwrap() > 0);
+    });
+}
+
+#[test]
+fn swapping_with_max_price_impact() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 10));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        // Swapping 10 tokens moves the pool from 50/50 to 60/43, which is 28.33%
+        assert_ok!(Dex::set_default_max_price_impact(
+            Origin::root(),
+            Some(Permill::from_parts(283_333))
+        ));
+        assert_noop!(
+            Dex::swap_token(Origin::signed(2), 314159265, 0, 10, 2, None),
+            Error::<Test>::PriceImpactTooHigh
+        );
+
+        // The pool bound overrides the default one
+        assert_ok!(Dex::set_pool_max_price_impact(
+            Origin::root(),
+            314159265,
+            Some(Permill::from_parts(283_334))
+        ));
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            10,
+            2,
+            None
+        ));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(7));
+    });
+}
+
+#[test]
+fn setting_max_price_impact() {
+    new_test_ext().execute_with(|| {
+        assert_noop!(
+            Dex::set_default_max_price_impact(Origin::signed(1), Some(Permill::one())),
+            sp_runtime::DispatchError::BadOrigin
+        );
+        assert_noop!(
+            Dex::set_pool_max_price_impact(Origin::root(), 314159265, None),
+            Error::<Test>::NoSuchPool
+        );
+        assert_ok!(Dex::set_default_max_price_impact(
+            Origin::root(),
+            Some(Permill::from_percent(5))
+        ));
+        assert_eq!(
+            Dex::get_default_max_price_impact(),
+            Some(Permill::from_percent(5))
+        );
+        assert_ok!(Dex::set_default_max_price_impact(Origin::root(), None));
+        assert_eq!(Dex::get_default_max_price_impact(), None);
+    });
+}
+
+#[test]
+fn rejecting_swaps_by_circuit_breaker() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 50));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::set_circuit_breaker_threshold(
+            Origin::root(),
+            Some(Permill::from_percent(50))
+        ));
+
+        // The price moves by 28% and then by 46% from the block start
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            10,
+            2,
+            None
+        ));
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            10,
+            2,
+            None
+        ));
+
+        // This swap would move the price by 56.25%, so it is rejected before it is executed
+        assert_noop!(
+            Dex::swap_token(Origin::signed(2), 314159265, 0, 10, 2, None),
+            Error::<Test>::CircuitBreakerActive
+        );
+        // A single large swap can not cross the threshold either
+        assert_noop!(
+            Dex::swap_token(Origin::signed(2), 314159265, 0, 30, 2, None),
+            Error::<Test>::CircuitBreakerActive
+        );
+
+        // The block start price resets in the next block
+        System::set_block_number(2);
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            10,
+            2,
+            None
+        ));
+        let start_price = Dex::get_block_start_price(314159265).unwrap();
+        assert_eq!(start_price.block, 2);
+        assert_eq!(start_price.first_asset_balance, 70);
+    });
+}
+
+#[test]
+fn swapping_with_dynamic_fee() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100000));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100000));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, 2000));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50000,
+            1,
+            50000,
+            Dex::default_fee_tier()
+        ));
+        assert_noop!(
+            Dex::set_dynamic_fee(
+                Origin::root(),
+                314159265,
+                Some((Permill::from_percent(2), Permill::from_percent(1)))
+            ),
+            Error::<Test>::InvalidFeeBounds
+        );
+        assert_ok!(Dex::set_dynamic_fee(
+            Origin::root(),
+            314159265,
+            Some((Permill::from_parts(1000), Permill::from_percent(1)))
+        ));
+
+        // There was no volatility yet, so the fee is minimal
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            1000,
+            2,
+            None
+        ));
+        System::assert_last_event(Event::Dex(crate::Event::Swapped {
+            operator: 2,
+            recipient: 2,
+            pool_account: 314159265,
+            first_asset: 0,
+            first_asset_amount: 1000,
+            second_asset: 1,
+            second_asset_amount: 979,
+            fee: Permill::from_parts(1000),
+        }));
+        assert!(!Dex::get_dynamic_fee(314159265)
+            .unwrap()
+            .volatility
+            .is_zero());
+
+        // The swap above moved the price by ~3.9%, so the fee reaches the maximum
+        assert_ok!(Dex::swap_token(
+            Origin::signed(2),
+            314159265,
+            0,
+            1000,
+            2,
+            None
+        ));
+        assert_eq!(
+            Dex::get_dynamic_fee(314159265).unwrap().current_fee(),
+            Permill::from_percent(1)
+        );
+        match System::events().last().unwrap().event {
+            Event::Dex(crate::Event::Swapped { fee, .. }) => {
+                assert_eq!(fee, Permill::from_percent(1))
+            }
+            _ => panic!("Expected Swapped event"),
+        }
+
+        assert_ok!(Dex::set_dynamic_fee(Origin::root(), 314159265, None));
+        assert_eq!(Dex::get_dynamic_fee(314159265), None);
+    });
+}
+
+#[test]
+fn pools_with_fee_tiers() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100000));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100000));
+        assert_noop!(
+            Dex::init(
+                Origin::signed(1),
+                314159265,
+                0,
+                10000,
+                1,
+                10000,
+                Permill::from_percent(1)
+            ),
+            Error::<Test>::FeeTierNotAllowed
+        );
+        assert_ok!(Dex::add_fee_tier(Origin::root(), Permill::from_percent(1)));
+        assert_ok!(Dex::add_fee_tier(Origin::root(), Permill::from_parts(500)));
+        assert_noop!(
+            Dex::add_fee_tier(Origin::root(), Dex::default_fee_tier()),
+            Error::<Test>::FeeTierAlreadyExists
+        );
+
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            10000,
+            1,
+            10000,
+            Permill::from_percent(1)
+        ));
+        System::assert_last_event(Event::Dex(crate::Event::PoolCreated {
+            creator: 1,
+            pool_account: 314159265,
+            first_asset: 0,
+            second_asset: 1,
+            fee_tier: Permill::from_percent(1),
+        }));
+        // The pair is the same regardless of the order of assets
+        assert_noop!(
+            Dex::init(
+                Origin::signed(1),
+                271828182,
+                1,
+                10000,
+                0,
+                10000,
+                Permill::from_percent(1)
+            ),
+            Error::<Test>::PoolAlreadyExists
+        );
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            271828182,
+            1,
+            10000,
+            0,
+            10000,
+            Permill::from_parts(500)
+        ));
+        assert_eq!(
+            Dex::get_pool_by_pair((0, 1), Permill::from_parts(500)),
+            Some(271828182)
+        );
+
+        // Both pools have the same reserves, so the lowest fee gives the best output
+        assert_eq!(Dex::best_pool_for_pair(0, 1, 1000), Some((271828182, 908)));
+        assert_eq!(
+            Dex::quote_exact_in(&314159265, 0, 1000),
+            Ok((1, 899, Permill::from_percent(1)))
+        );
+
+        assert_ok!(Dex::remove_fee_tier(
+            Origin::root(),
+            Permill::from_percent(1)
+        ));
+        assert_noop!(
+            Dex::remove_fee_tier(Origin::root(), Permill::from_percent(1)),
+            Error::<Test>::NoSuchFeeTier
+        );
+        assert_noop!(
+            Dex::remove_fee_tier(Origin::root(), Dex::default_fee_tier()),
+            Error::<Test>::DefaultFeeTierNotRemovable
+        );
+        // Existing pools of a removed tier are still usable
+        assert_ok!(Dex::swap_token(
+            Origin::signed(1),
+            314159265,
+            0,
+            1000,
+            1,
+            None
+        ));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(80899));
+    });
+}
+
+#[test]
+fn swapping_through_best_route() {
+    new_test_ext().execute_with(|| {
+        for (id, amount) in [(0, 106100), (1, 10000), (2, 200000), (3, 106000)] {
+            assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+            assert_ok!(MultiTokenPallet::mint(Origin::signed(1), id, amount));
+        }
+        // Direct pool with low liquidity
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            103,
+            0,
+            1000,
+            3,
+            1000,
+            Dex::default_fee_tier()
+        ));
+        // Two routes through asset 1 and asset 2
+        for (pool, first_asset, second_asset, amount) in [
+            (101, 0, 1, 5000),
+            (113, 1, 3, 5000),
+            (102, 0, 2, 100000),
+            (123, 2, 3, 100000),
+        ] {
+            assert_ok!(Dex::init(
+                Origin::signed(1),
+                pool,
+                first_asset,
+                amount,
+                second_asset,
+                amount,
+                Dex::default_fee_tier()
+            ));
+        }
+
+        // Direct swap gives 89, the route through asset 1 gives 94 and through asset 2 gives 96
+        assert_eq!(Dex::find_best_route(0, 3, 100), Some((vec![102, 123], 96)));
+        assert_noop!(
+            Dex::swap_exact_in_auto(Origin::signed(1), 0, 3, 100, 97, 2, None),
+            Error::<Test>::SlippageExceeded
+        );
+        assert_ok!(Dex::swap_exact_in_auto(
+            Origin::signed(1),
+            0,
+            3,
+            100,
+            96,
+            2,
+            None
+        ));
+        assert_eq!(MultiTokenPallet::get_balance(&3, &2), Some(96));
+        assert_eq!(MultiTokenPallet::get_balance(&2, &1).unwrap_or_default(), 0);
+        System::assert_last_event(Event::Dex(crate::Event::RouteSwapped {
+            operator: 1,
+            recipient: 2,
+            route: vec![102, 123],
+            asset_in: 0,
+            amount_in: 100,
+            asset_out: 3,
+            amount_out: 96,
+        }));
+    });
+}
+
+#[test]
+fn route_longer_than_max_hops() {
+    new_test_ext().execute_with(|| {
+        for id in 0..4 {
+            assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+            assert_ok!(MultiTokenPallet::mint(Origin::signed(1), id, 2000));
+        }
+        for (pool, first_asset, second_asset) in [(101, 0, 1), (112, 1, 2), (123, 2, 3)] {
+            assert_ok!(Dex::init(
+                Origin::signed(1),
+                pool,
+                first_asset,
+                1000,
+                second_asset,
+                1000,
+                Dex::default_fee_tier()
+            ));
+        }
+
+        assert!(Dex::find_best_route(0, 2, 100).is_some());
+        assert_eq!(Dex::find_best_route(0, 3, 100), None);
+        assert_noop!(
+            Dex::swap_exact_in_auto(Origin::signed(1), 0, 3, 100, 0, 1, None),
+            Error::<Test>::NoRouteFound
+        );
+    });
+}
+
+#[test]
+fn swapping_split_across_routes() {
+    new_test_ext().execute_with(|| {
+        for (id, amount) in [(0, 2200), (1, 2000), (2, 2000)] {
+            assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+            assert_ok!(MultiTokenPallet::mint(Origin::signed(1), id, amount));
+        }
+        for (pool, first_asset, second_asset) in [(101, 0, 1), (102, 0, 2), (121, 2, 1)] {
+            assert_ok!(Dex::init(
+                Origin::signed(1),
+                pool,
+                first_asset,
+                1000,
+                second_asset,
+                1000,
+                Dex::default_fee_tier()
+            ));
+        }
+
+        // The route has to end with the output asset
+        let invalid_routes: Vec<(BoundedVec<u64, ConstU32<2>>, u128)> =
+            vec![(vec![102].try_into().unwrap(), 100)];
+        assert_noop!(
+            Dex::swap_split(
+                Origin::signed(1),
+                0,
+                1,
+                invalid_routes.try_into().unwrap(),
+                0,
+                2,
+                None
+            ),
+            Error::<Test>::InvalidRoute
+        );
+        // Swapping 200 directly gives 165, splitting gives 89 + 80
+        assert_eq!(
+            Dex::quote_exact_in(&101, 0, 200).map(|(_, output, _)| output),
+            Ok(165)
+        );
+        let routes: Vec<(BoundedVec<u64, ConstU32<2>>, u128)> = vec![
+            (vec![101].try_into().unwrap(), 100),
+            (vec![102, 121].try_into().unwrap(), 100),
+        ];
+        assert_noop!(
+            Dex::swap_split(
+                Origin::signed(1),
+                0,
+                1,
+                routes.clone().try_into().unwrap(),
+                170,
+                2,
+                None
+            ),
+            Error::<Test>::SlippageExceeded
+        );
+        assert_ok!(Dex::swap_split(
+            Origin::signed(1),
+            0,
+            1,
+            routes.try_into().unwrap(),
+            169,
+            2,
+            None
+        ));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(169));
+        System::assert_last_event(Event::Dex(crate::Event::SplitSwapped {
+            operator: 1,
+            recipient: 2,
+            routes: 2,
+            asset_in: 0,
+            amount_in: 200,
+            asset_out: 1,
+            amount_out: 169,
+        }));
+    });
+}
+
+#[test]
+fn batch_liquidity_operations() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+
+        // A failing operation reverts the whole batch
+        let failing_operations = vec![
+            LiquidityOperation::Add {
+                pool: 314159265,
+                token_id: 0,
+                amount: 10,
+            },
+            LiquidityOperation::Swap {
+                pool: 271828182,
+                token_id: 0,
+                amount: 10,
+            },
+        ];
+        assert_noop!(
+            Dex::batch_liquidity(
+                Origin::signed(1),
+                failing_operations.try_into().unwrap(),
+                None
+            ),
+            Error::<Test>::NoSuchPool
+        );
+
+        let operations = vec![
+            LiquidityOperation::Add {
+                pool: 314159265,
+                token_id: 0,
+                amount: 10,
+            },
+            LiquidityOperation::Remove {
+                pool: 314159265,
+                token_id: 0,
+                amount: 10,
+            },
+            LiquidityOperation::Swap {
+                pool: 314159265,
+                token_id: 0,
+                amount: 10,
+            },
+        ];
+        assert_eq!(
+            Dex::batch_liquidity_weight(&operations),
+            <() as WeightInfo>::batch_liquidity(3)
+                + <() as WeightInfo>::deposit()
+                + <() as WeightInfo>::withdraw()
+                + <() as WeightInfo>::swap_token()
+        );
+        assert_ok!(Dex::batch_liquidity(
+            Origin::signed(1),
+            operations.try_into().unwrap(),
+            None
+        ));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &1), Some(40));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(57));
+        assert_eq!(Dex::get_pool_share(314159265, 1), Some(10000));
+        System::assert_has_event(Event::Dex(crate::Event::BatchOperationCompleted {
+            index: 0,
+            amount: 10,
+        }));
+        System::assert_has_event(Event::Dex(crate::Event::BatchOperationCompleted {
+            index: 2,
+            amount: 7,
+        }));
+        System::assert_last_event(Event::Dex(crate::Event::BatchCompleted {
+            operator: 1,
+            operations: 3,
+        }));
+    });
+}
+
+#[test]
+fn removing_liquidity_to_one_asset() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100000000));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100000000));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50000000,
+            1,
+            50000000,
+            Dex::default_fee_tier()
+        ));
+
+        // 5000000 of each asset is withdrawn and 5000000 of asset 1 is swapped
+        assert_eq!(
+            Dex::quote_remove_liquidity_one_asset(&314159265, 1000, 0),
+            Ok(9486500)
+        );
+        assert_noop!(
+            Dex::remove_liquidity_one_asset(
+                Origin::signed(1),
+                314159265,
+                1000,
+                0,
+                9486501,
+                1,
+                None
+            ),
+            Error::<Test>::SlippageExceeded
+        );
+        assert_noop!(
+            Dex::remove_liquidity_one_asset(Origin::signed(2), 314159265, 1000, 0, 0, 2, None),
+            Error::<Test>::NotEnoughShares
+        );
+        assert_ok!(Dex::remove_liquidity_one_asset(
+            Origin::signed(1),
+            314159265,
+            1000,
+            0,
+            9486500,
+            1,
+            None
+        ));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &1), Some(59486500));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &1), Some(50000000));
+        assert_eq!(Dex::get_pool_share(314159265, 1), Some(9000));
+        assert_eq!(Dex::get_total_pool_shares(314159265), Some(9000));
+        // The withdrawal is reported after the swap, once the whole operation has succeeded
+        let events = System::events();
+        assert!(matches!(
+            events[events.len() - 3].event,
+            Event::Dex(crate::Event::Swapped { .. })
+        ));
+        assert_eq!(
+            events[events.len() - 2].event,
+            Event::Dex(crate::Event::Withdrawed {
+                operator: 1,
+                recipient: 1,
+                pool_account: 314159265,
+                first_asset: 0,
+                first_asset_amount: 5000000,
+                second_asset: 1,
+                second_asset_amount: 5000000,
+            })
+        );
+        System::assert_last_event(Event::Dex(crate::Event::WithdrawedOneAsset {
+            operator: 1,
+            recipient: 1,
+            pool_account: 314159265,
+            shares: 1000,
+            asset: 0,
+            amount: 9486500,
+        }));
+    });
+}
+
+#[test]
+fn removing_all_liquidity_to_one_asset() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            40,
+            Dex::default_fee_tier()
+        ));
+
+        // The last liquidity provider empties the pool, the other asset is paid out as it is
+        assert_eq!(
+            Dex::quote_remove_liquidity_one_asset(&314159265, 10000, 0),
+            Ok(50)
+        );
+        assert_ok!(Dex::remove_liquidity_one_asset(
+            Origin::signed(1),
+            314159265,
+            10000,
+            0,
+            50,
+            2,
+            None
+        ));
+
+        assert_eq!(MultiTokenPallet::get_balance(&0, &2), Some(50));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &2), Some(40));
+        assert_eq!(MultiTokenPallet::get_balance(&0, &314159265), Some(0));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &314159265), Some(0));
+        assert_eq!(Dex::get_pool_share(314159265, 1), Some(0));
+        assert_eq!(Dex::get_total_pool_shares(314159265), Some(0));
+        assert_ok!(Dex::do_try_state());
+        System::assert_last_event(Event::Dex(crate::Event::WithdrawedOneAsset {
+            operator: 1,
+            recipient: 2,
+            pool_account: 314159265,
+            shares: 10000,
+            asset: 0,
+            amount: 50,
+        }));
+    });
+}
+
+#[test]
+fn pools_with_large_reserves() {
+    new_test_ext().execute_with(|| {
+        let reserve = 10_u128.pow(36);
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 3 * reserve));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 3 * reserve));
+        // The pool constant does not fit into `u128`
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            reserve,
+            1,
+            reserve,
+            Dex::default_fee_tier()
+        ));
+        assert_eq!(
+            Dex::get_pool(314159265).unwrap().constant,
+            U256::from(reserve) * U256::from(reserve)
+        );
+
+        assert_ok!(Dex::swap_token(
+            Origin::signed(1),
+            314159265,
+            0,
+            reserve / 100,
+            1,
+            None
+        ));
+        assert_eq!(
+            MultiTokenPallet::get_balance(&1, &1),
+            Some(2 * reserve + 9871287128712871287128712871287128)
+        );
+
+        assert_ok!(Dex::deposit(
+            Origin::signed(1),
+            314159265,
+            0,
+            reserve / 10,
+            None
+        ));
+        assert_eq!(Dex::get_pool_share(314159265, 1), Some(10990));
+        assert_eq!(
+            MultiTokenPallet::get_balance(&1, &314159265),
+            Some(
+                reserve - 9871287128712871287128712871287128 + 98032545828840309773551612587001275
+            )
+        );
+
+        assert_ok!(Dex::withdraw(
+            Origin::signed(1),
+            314159265,
+            0,
+            reserve / 10,
+            1,
+            None
+        ));
+        // The burned share is rounded up
+        assert_eq!(Dex::get_pool_share(314159265, 1), Some(9999));
+    });
+}
+
+#[test]
+fn checking_pool_invariants() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            0,
+            50,
+            1,
+            50,
+            Dex::default_fee_tier()
+        ));
+        assert_ok!(Dex::do_try_state());
+
+        // Tokens transferred to the pool directly only increase the product of balances
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(1),
+            1,
+            314159265,
+            0,
+            10
+        ));
+        assert_ok!(Dex::do_try_state());
+
+        crate::TotalPoolShares::<Test>::insert(314159265, 10001);
+        assert_eq!(
+            Dex::do_try_state(),
+            Err("Total pool share does not match the sum of pool shares")
+        );
+        crate::TotalPoolShares::<Test>::insert(314159265, 10000);
+
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(314159265),
+            314159265,
+            1,
+            1,
+            10
+        ));
+        assert_eq!(
+            Dex::do_try_state(),
+            Err("Pool constant is higher than the product of pool balances")
+        );
+    });
+}
+
+#[test]
+fn creating_pools_at_genesis() {
+    new_test_ext().execute_with(|| {
+        for _ in 0..3 {
+            assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        }
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 2, 100));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, 50));
+        assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 2, 100));
+
+        GenesisBuild::<Test>::build(&crate::GenesisConfig::<Test> {
+            fee_tiers: vec![Permill::from_percent(1)],
+            pools: vec![
+                (1, 101, 0, 50, 1, 40, Dex::default_fee_tier()),
+                (2, 112, 1, 50, 2, 100, Permill::from_percent(1)),
+            ],
+        });
+
+        assert_eq!(
+            Dex::get_pool_by_pair((0, 1), Dex::default_fee_tier()),
+            Some(101)
+        );
+        assert_eq!(
+            Dex::get_pool_by_pair((1, 2), Permill::from_percent(1)),
+            Some(112)
+        );
+        assert_eq!(MultiTokenPallet::get_balance(&0, &101), Some(50));
+        assert_eq!(MultiTokenPallet::get_balance(&1, &101), Some(40));
+        assert_eq!(MultiTokenPallet::get_balance(&2, &112), Some(100));
+        assert_eq!(Dex::get_pool_share(112, 2), Some(10000));
+        assert_eq!(Balances::reserved_balance(1), 10);
+        assert_ok!(Dex::do_try_state());
+    });
+}
+
+#[test]
+#[should_panic(expected = "Genesis pool 1 can not be created")]
+fn creating_invalid_pool_at_genesis() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+
+        // The second pool has the same pair and fee tier
+        GenesisBuild::<Test>::build(&crate::GenesisConfig::<Test> {
+            fee_tiers: vec![],
+            pools: vec![
+                (1, 101, 0, 10, 1, 10, Dex::default_fee_tier()),
+                (1, 102, 1, 10, 0, 10, Dex::default_fee_tier()),
+            ],
+        });
+    });
+}
+
+fn init_pool_for_properties(first_reserve: u128, second_reserve: u128) {
+    assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+    assert_ok!(MultiTokenPallet::mint(
+        Origin::signed(1),
+        0,
+        2 * first_reserve
+    ));
+    assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+    assert_ok!(MultiTokenPallet::mint(
+        Origin::signed(1),
+        1,
+        2 * second_reserve
+    ));
+    assert_ok!(Dex::init(
+        Origin::signed(1),
+        314159265,
+        0,
+        first_reserve,
+        1,
+        second_reserve,
+        Dex::default_fee_tier()
+    ));
+}
+
+fn pool_product() -> U256 {
+    let first = MultiTokenPallet::get_balance(&0, &314159265).unwrap_or_default();
+    let second = MultiTokenPallet::get_balance(&1, &314159265).unwrap_or_default();
+    U256::from(first) * U256::from(second)
+}
+
+proptest! {
+    #[test]
+    fn product_never_decreases_on_swaps(
+        first_reserve in 1_000_u128..1_000_000_000_000,
+        second_reserve in 1_000_u128..1_000_000_000_000,
+        swaps in prop::collection::vec((0_u32..2, 1_u128..1_000_000_000), 1..20),
+    ) {
+        new_test_ext().execute_with(|| {
+            init_pool_for_properties(first_reserve, second_reserve);
+            let mut product = pool_product();
+            for (token_id, amount) in swaps {
+                // Failed swaps leave the pool untouched, so only the product is checked
+                let _ = Dex::swap_token(Origin::signed(1), 314159265, token_id, amount, 1, None);
+                let new_product = pool_product();
+                prop_assert!(new_product >= product);
+                product = new_product;
+            }
+            Ok(())
+        })?;
+    }
+
+    #[test]
+    fn liquidity_round_trip_does_not_extract_value(
+        first_reserve in 1_000_u128..1_000_000_000_000,
+        second_reserve in 1_000_u128..1_000_000_000_000,
+        amount in 1_u128..1_000_000_000_000,
+    ) {
+        new_test_ext().execute_with(|| {
+            init_pool_for_properties(first_reserve, second_reserve);
+            assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 0, first_reserve));
+            assert_ok!(MultiTokenPallet::transfer(Origin::signed(1), 1, 2, 1, second_reserve));
+
+            let amount = amount.min(first_reserve);
+            if Dex::deposit(Origin::signed(2), 314159265, 0, amount, None).is_ok() {
+                // The withdrawal can fail if the rounded up burn exceeds the minted share
+                let _ = Dex::withdraw(Origin::signed(2), 314159265, 0, amount, 2, None);
+            }
+            let first_balance = MultiTokenPallet::get_balance(&0, &2).unwrap_or_default();
+            let second_balance = MultiTokenPallet::get_balance(&1, &2).unwrap_or_default();
+            prop_assert!(first_balance <= first_reserve);
+            prop_assert!(second_balance <= second_reserve);
+            Ok(())
+        })?;
+    }
+}
+
+fn operation() -> impl Strategy<Value = Operation> {
+    let amount = 1_u128..1_000_000_000_000;
+    prop_oneof![
+        (
+            any::<u8>(),
+            any::<u8>(),
+            any::<u8>(),
+            amount.clone(),
+            any::<u8>(),
+            amount.clone()
+        )
+            .prop_map(
+                |(user, pool, first_asset, first_amount, second_asset, second_amount)| {
+                    Operation::Init {
+                        user,
+                        pool,
+                        first_asset,
+                        first_amount,
+                        second_asset,
+                        second_amount,
+                    }
+                }
+            ),
+        (any::<u8>(), any::<u8>(), any::<u8>(), amount.clone()).prop_map(
+            |(user, pool, asset, amount)| Operation::Swap {
+                user,
+                pool,
+                asset,
+                amount
+            }
+        ),
+        (any::<u8>(), any::<u8>(), any::<u8>(), amount.clone()).prop_map(
+            |(user, pool, asset, amount)| Operation::Deposit {
+                user,
+                pool,
+                asset,
+                amount
+            }
+        ),
+        (any::<u8>(), any::<u8>(), any::<u8>(), amount.clone()).prop_map(
+            |(user, pool, asset, amount)| Operation::Withdraw {
+                user,
+                pool,
+                asset,
+                amount
+            }
+        ),
+        (any::<u8>(), any::<u8>(), any::<u8>(), amount.clone()).prop_map(
+            |(user, pool, asset, amount)| Operation::DepositOneAsset {
+                user,
+                pool,
+                asset,
+                amount
+            }
+        ),
+        (any::<u8>(), any::<u8>(), any::<u8>(), amount).prop_map(|(user, pool, asset, amount)| {
+            Operation::WithdrawOneAsset {
+                user,
+                pool,
+                asset,
+                amount,
+            }
+        }),
+        (any::<u8>(), any::<u8>(), 1_u128..20_000, any::<u8>()).prop_map(
+            |(user, pool, shares, asset)| Operation::RemoveLiquidityOneAsset {
+                user,
+                pool,
+                shares,
+                asset
+            }
+        ),
+    ]
+}
+
+proptest! {
+    // Random sequences of operations keep every pool invariant, see `fuzzing::run`
+    #[test]
+    fn random_operations_keep_invariants(
+        operations in prop::collection::vec(operation(), 1..40),
+    ) {
+        fuzzing::run(&operations);
+    }
+}
+
+#[test]
+fn migrating_pools_to_pool_info() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(1),
+            1,
+            314159265,
+            0,
+            50
+        ));
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(1),
+            1,
+            314159265,
+            1,
+            40
+        ));
+        // A pool stored as a pair of assets and the pool constant
+        unhashed::put(
+            &crate::Pools::<Test>::hashed_key_for(&314159265),
+            &(0u64, 1u64, 2000u128),
+        );
+        crate::TotalPoolShares::<Test>::insert(&314159265, 10000);
+        crate::PoolShares::<Test>::insert(&314159265, &1, 10000);
+
+        crate::migrations::v1::migrate::<Test>();
+
+        assert_eq!(
+            Dex::get_pool(314159265),
+            Some(PoolInfo {
+                first_asset: 0,
+                second_asset: 1,
+                constant: U256::from(2000),
+                creator: 314159265,
+                deposit: 0,
+                status: PoolStatus::Live,
+                fee_tier: Dex::default_fee_tier(),
+            })
+        );
+        assert_eq!(
+            Dex::get_pool_by_pair((0, 1), Dex::default_fee_tier()),
+            Some(314159265)
+        );
+        assert_eq!(Dex::get_asset_pool(0, 314159265), Some(()));
+        assert_eq!(Dex::get_asset_pool(1, 314159265), Some(()));
+
+        // The pool can be traded with after the migration
+        assert_ok!(Dex::swap_token(
+            Origin::signed(1),
+            314159265,
+            0,
+            10,
+            1,
+            None
+        ));
+        assert_ok!(Dex::do_try_state());
+    });
+}
+
+#[test]
+fn destroying_unindexed_migrated_pool() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        // Two pools of the same pair stored before fee tiers existed
+        for (pool, amount) in [(314159265, 50), (271828182, 30)] {
+            assert_ok!(MultiTokenPallet::transfer(
+                Origin::signed(1),
+                1,
+                pool,
+                0,
+                amount
+            ));
+            assert_ok!(MultiTokenPallet::transfer(
+                Origin::signed(1),
+                1,
+                pool,
+                1,
+                amount
+            ));
+            unhashed::put(
+                &crate::Pools::<Test>::hashed_key_for(&pool),
+                &(0u64, 1u64, amount * amount),
+            );
+        }
+        crate::migrations::v1::migrate::<Test>();
+
+        // Only one of them is in the pair index, destroying the other one keeps its entry
+        let indexed = Dex::get_pool_by_pair((0, 1), Dex::default_fee_tier()).unwrap();
+        let unindexed = if indexed == 314159265 {
+            271828182
+        } else {
+            314159265
+        };
+        assert_ok!(Dex::destroy_pool(Origin::signed(2), unindexed));
+
+        assert_eq!(Dex::get_pool(unindexed), None);
+        assert_eq!(
+            Dex::get_pool_by_pair((0, 1), Dex::default_fee_tier()),
+            Some(indexed)
+        );
+        assert_eq!(
+            Dex::best_pool_for_pair(0, 1, 10).map(|(pool, _)| pool),
+            Some(indexed)
+        );
+    });
+}
+
+#[test]
+fn registering_providers_of_existing_pools() {
+    new_test_ext().execute_with(|| {
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 0, 100));
+        assert_ok!(MultiTokenPallet::create(Origin::signed(1)));
+        assert_ok!(MultiTokenPallet::mint(Origin::signed(1), 1, 100));
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(1),
+            1,
+            314159265,
+            0,
+            50
+        ));
+        assert_ok!(MultiTokenPallet::transfer(
+            Origin::signed(1),
+            1,
+            314159265,
+            1,
+            50
+        ));
+        // A pool of the old layout that has been created before the pallet became its provider
+        unhashed::put(
+            &crate::Pools::<Test>::hashed_key_for(&314159265),
+            &(0u64, 1u64, 2500u128),
+        );
+        crate::TotalPoolShares::<Test>::insert(&314159265, 10000);
+        crate::PoolShares::<Test>::insert(&314159265, &1, 10000);
+        StorageVersion::new(0).put::<Dex>();
+        assert_eq!(System::providers(&314159265), 0);
+
+        Dex::on_runtime_upgrade();
+        assert_eq!(System::providers(&314159265), 1);
+        assert_eq!(Dex::on_chain_storage_version(), 1);
+        assert_eq!(
+            Dex::get_pool(314159265).map(|pool_info| pool_info.status),
+            Some(PoolStatus::Live)
+        );
+        assert_ok!(Dex::do_try_state());
+
+        // The migration is not applied twice
+        Dex::on_runtime_upgrade();
+        assert_eq!(System::providers(&314159265), 1);
+    });
+}
+
+#[test]
+fn setting_allow_death() {
+    new_test_ext().execute_with(|| {
+        System::set_block_number(1);
+        assert!(!Dex::is_death_allowed(&2));
+        assert_ok!(Dex::set_allow_death(Origin::signed(2), true));
+        assert!(Dex::is_death_allowed(&2));
+        System::assert_last_event(Event::Dex(crate::Event::AllowDeathSet {
+            account: 2,
+            allow_death: true,
+        }));
+
+        assert_ok!(Dex::set_allow_death(Origin::signed(2), false));
+        assert!(!Dex::is_death_allowed(&2));
+    });
+}
+
+// The same pools on top of `pallet_assets` and the native currency
+mod assets_backend {
+    use crate::{mock_assets::*, NativeOrAsset};
+    use frame_support::{
+        assert_noop, assert_ok,
+        traits::{tokens::fungibles::Inspect, LockableCurrency, WithdrawReasons},
+    };
+
+    const NATIVE: NativeOrAsset<u64> = NativeOrAsset::Native;
+    const FIRST_ASSET: NativeOrAsset<u64> = NativeOrAsset::Asset(0);
+    const SECOND_ASSET: NativeOrAsset<u64> = NativeOrAsset::Asset(1);
+    const THIRD_ASSET: NativeOrAsset<u64> = NativeOrAsset::Asset(2);
+
+    fn balance(asset: NativeOrAsset<u64>, who: u64) -> u128 {
+        <Test as crate::Config>::Assets::balance(asset, &who)
+    }
+
+    fn create_assets() {
+        for asset in [0, 1] {
+            assert_ok!(Assets::force_create(Origin::root(), asset, 1, true, 1));
+            assert_ok!(Assets::mint(Origin::signed(1), asset, 1, 100));
+        }
+    }
+
+    fn init_pool(first_asset: NativeOrAsset<u64>, second_asset: NativeOrAsset<u64>) {
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            314159265,
+            first_asset,
+            50,
+            second_asset,
+            50,
+            Dex::default_fee_tier()
+        ));
+    }
+
+    // The first and the second asset are connected only through the third asset,
+    // by the pools 314159265 and 271828182
+    fn init_route_pools() {
+        create_assets();
+        assert_ok!(Assets::force_create(Origin::root(), 2, 1, true, 1));
+        assert_ok!(Assets::mint(Origin::signed(1), 2, 1, 200));
+        init_pool(FIRST_ASSET, THIRD_ASSET);
+        assert_ok!(Dex::init(
+            Origin::signed(1),
+            271828182,
+            THIRD_ASSET,
+            50,
+            SECOND_ASSET,
+            50,
+            Dex::default_fee_tier()
+        ));
+    }
+
+    #[test]
+    fn swap_tokens() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            init_pool(FIRST_ASSET, SECOND_ASSET);
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 10));
+            // By default the operator keeps the minimum balance of the asset
+            assert_noop!(
+                Dex::swap_token(Origin::signed(2), 314159265, FIRST_ASSET, 10, 2, None),
+                crate::Error::<Test>::NotEnoughBalance
+            );
+
+            assert_ok!(Dex::set_allow_death(Origin::signed(2), true));
+            assert_ok!(Dex::swap_token(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                10,
+                2,
+                None
+            ));
+
+            assert_eq!(balance(FIRST_ASSET, 2), 0);
+            assert_eq!(balance(SECOND_ASSET, 2), 7);
+            assert_eq!(balance(FIRST_ASSET, 314159265), 60);
+            assert_eq!(balance(SECOND_ASSET, 314159265), 43);
+        });
+    }
+
+    #[test]
+    fn depositing_and_withdrawing_liquidity() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            init_pool(FIRST_ASSET, SECOND_ASSET);
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 10));
+            assert_ok!(Assets::transfer(Origin::signed(1), 1, 2, 10));
+            assert_ok!(Dex::set_allow_death(Origin::signed(2), true));
+            assert_ok!(Dex::deposit(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                10,
+                None
+            ));
+
+            assert_eq!(balance(FIRST_ASSET, 2), 0);
+            assert_eq!(balance(SECOND_ASSET, 2), 0);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(2000));
+            assert_eq!(Dex::get_total_pool_shares(314159265), Some(12000));
+
+            assert_ok!(Dex::withdraw(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                10,
+                2,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 10);
+            assert_eq!(balance(SECOND_ASSET, 2), 10);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(0));
+            assert_ok!(Dex::do_try_state());
+        });
+    }
+
+    #[test]
+    fn depositing_more_than_balance() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            init_pool(FIRST_ASSET, SECOND_ASSET);
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 10));
+            assert_ok!(Assets::transfer(Origin::signed(1), 1, 2, 5));
+            assert_noop!(
+                Dex::deposit(Origin::signed(2), 314159265, FIRST_ASSET, 10, None),
+                crate::Error::<Test>::NotEnoughBalance
+            );
+        });
+    }
+
+    #[test]
+    fn native_asset_pool() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            init_pool(NATIVE, FIRST_ASSET);
+            // The creation deposit is reserved and 50 are in the pool
+            assert_eq!(Balances::free_balance(1), 40);
+            assert_eq!(Balances::reserved_balance(1), 10);
+            assert_eq!(balance(NATIVE, 314159265), 50);
+
+            // Swapping the native currency
+            assert_ok!(Dex::swap_token(
+                Origin::signed(2),
+                314159265,
+                NATIVE,
+                10,
+                2,
+                None
+            ));
+            assert_eq!(Balances::free_balance(2), 90);
+            assert_eq!(balance(FIRST_ASSET, 2), 7);
+
+            // Swapping back into the native currency
+            assert_ok!(Dex::set_allow_death(Origin::signed(2), true));
+            assert_ok!(Dex::swap_token(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                7,
+                2,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 0);
+            assert_eq!(Balances::free_balance(2), 97);
+
+            // Depositing and withdrawing with the native currency as the corresponding asset
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 10));
+            assert_ok!(Dex::deposit(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                10,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 0);
+            assert_eq!(Balances::free_balance(2), 86);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(2000));
+
+            assert_ok!(Dex::withdraw(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                10,
+                2,
+                None
+            ));
+            assert_eq!(Balances::free_balance(2), 96);
+            assert_eq!(balance(FIRST_ASSET, 2), 10);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(0));
+            assert_ok!(Dex::do_try_state());
+        });
+    }
+
+    #[test]
+    fn destroying_frozen_pool_with_payouts_below_minimum_balance() {
+        new_test_ext().execute_with(|| {
+            System::set_block_number(1);
+            for asset in [0, 1] {
+                assert_ok!(Assets::force_create(Origin::root(), asset, 1, true, 10));
+                assert_ok!(Assets::mint(Origin::signed(1), asset, 1, 100));
+            }
+            init_pool(FIRST_ASSET, SECOND_ASSET);
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 3, 15));
+            assert_ok!(Assets::transfer(Origin::signed(1), 1, 3, 15));
+            assert_ok!(Dex::deposit(
+                Origin::signed(3),
+                314159265,
+                FIRST_ASSET,
+                5,
+                None
+            ));
+            // The second provider has no account in the assets anymore
+            assert_ok!(Assets::transfer(Origin::signed(3), 0, 1, 10));
+            assert_ok!(Assets::transfer(Origin::signed(3), 1, 1, 10));
+
+            assert_ok!(Dex::freeze_pool(Origin::root(), 314159265));
+            // The shares of account 3 are paid out first, its payout of 5 is below the minimum
+            // balance of 10 and is skipped instead of failing the call
+            assert_ok!(Dex::destroy_pool(Origin::root(), 314159265));
+            assert_eq!(balance(FIRST_ASSET, 3), 0);
+            assert_eq!(balance(FIRST_ASSET, 314159265), 55);
+            assert_ok!(Dex::destroy_pool(Origin::root(), 314159265));
+
+            // What has not been paid out goes to the remaining provider
+            assert_eq!(Dex::get_pool(314159265), None);
+            assert_eq!(balance(FIRST_ASSET, 1), 100);
+            assert_eq!(balance(SECOND_ASSET, 1), 100);
+            assert_eq!(balance(FIRST_ASSET, 314159265), 0);
+            assert_eq!(balance(SECOND_ASSET, 314159265), 0);
+            assert_eq!(System::providers(&314159265), 0);
+            System::assert_last_event(Event::Dex(crate::Event::PoolDestroyed {
+                pool_account: 314159265,
+                first_asset: FIRST_ASSET,
+                first_asset_dust: 0,
+                second_asset: SECOND_ASSET,
+                second_asset_dust: 0,
+            }));
+        });
+    }
+
+    #[test]
+    fn native_asset_with_locked_balance() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            init_pool(NATIVE, FIRST_ASSET);
+            Balances::set_lock(*b"stakelck", &2, 60, WithdrawReasons::all());
+
+            // Only the unlocked part of the free balance can be swapped
+            assert_noop!(
+                Dex::swap_token(Origin::signed(2), 314159265, NATIVE, 41, 2, None),
+                crate::Error::<Test>::NotEnoughBalance
+            );
+            assert_ok!(Dex::swap_token(
+                Origin::signed(2),
+                314159265,
+                NATIVE,
+                40,
+                2,
+                None
+            ));
+            assert_eq!(Balances::free_balance(2), 60);
+        });
+    }
+
+    #[test]
+    fn native_asset_pool_without_enough_balance() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            // The reserved creation deposit can not be put into the pool
+            assert_noop!(
+                Dex::init(
+                    Origin::signed(1),
+                    314159265,
+                    NATIVE,
+                    95,
+                    FIRST_ASSET,
+                    50,
+                    Dex::default_fee_tier()
+                ),
+                pallet_balances::Error::<Test>::InsufficientBalance
+            );
+        });
+    }
+
+    #[test]
+    fn pool_keeps_minimum_balance() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            // An asset that is not sufficient can only be held by accounts with a provider
+            assert_ok!(Assets::force_create(Origin::root(), 2, 1, false, 10));
+            assert_ok!(Assets::mint(Origin::signed(1), 2, 1, 100));
+            init_pool(FIRST_ASSET, NativeOrAsset::Asset(2));
+            assert_eq!(System::providers(&314159265), 1);
+
+            // 5 would be left in the pool, which is below the minimum balance of 10
+            assert_noop!(
+                Dex::withdraw(Origin::signed(1), 314159265, FIRST_ASSET, 45, 1, None),
+                pallet_assets::Error::<Test>::BalanceLow
+            );
+            assert_ok!(Dex::withdraw(
+                Origin::signed(1),
+                314159265,
+                FIRST_ASSET,
+                40,
+                1,
+                None
+            ));
+            assert_eq!(balance(NativeOrAsset::Asset(2), 314159265), 10);
+
+            // Taking everything is allowed, the pool account is still kept by the pallet
+            assert_ok!(Dex::withdraw(
+                Origin::signed(1),
+                314159265,
+                FIRST_ASSET,
+                10,
+                1,
+                None
+            ));
+            assert_eq!(balance(NativeOrAsset::Asset(2), 314159265), 0);
+            assert!(System::account_exists(&314159265));
+        });
+    }
+
+    #[test]
+    fn swapping_along_route_without_intermediate_asset() {
+        new_test_ext().execute_with(|| {
+            init_route_pools();
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 11));
+
+            // The operator has no account in the intermediate asset, the whole output of the
+            // first hop is swapped further although the operator is kept alive
+            assert_ok!(Dex::swap_exact_in_auto(
+                Origin::signed(2),
+                FIRST_ASSET,
+                SECOND_ASSET,
+                10,
+                5,
+                2,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 1);
+            assert_eq!(balance(THIRD_ASSET, 2), 0);
+            assert_eq!(balance(SECOND_ASSET, 2), 5);
+            assert_eq!(balance(THIRD_ASSET, 271828182), 57);
+            assert_eq!(balance(SECOND_ASSET, 271828182), 45);
+            assert_ok!(Dex::do_try_state());
+        });
+    }
+
+    #[test]
+    fn swapping_split_without_intermediate_asset() {
+        new_test_ext().execute_with(|| {
+            init_route_pools();
+            assert_ok!(Assets::mint(Origin::signed(1), 0, 1, 50));
+            assert_ok!(Assets::mint(Origin::signed(1), 1, 1, 50));
+            assert_ok!(Dex::init(
+                Origin::signed(1),
+                161803398,
+                FIRST_ASSET,
+                50,
+                SECOND_ASSET,
+                50,
+                Dex::default_fee_tier()
+            ));
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 21));
+
+            let routes = vec![
+                (vec![314159265, 271828182].try_into().unwrap(), 10),
+                (vec![161803398].try_into().unwrap(), 10),
+            ]
+            .try_into()
+            .unwrap();
+            assert_ok!(Dex::swap_split(
+                Origin::signed(2),
+                FIRST_ASSET,
+                SECOND_ASSET,
+                routes,
+                12,
+                2,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 1);
+            assert_eq!(balance(THIRD_ASSET, 2), 0);
+            assert_eq!(balance(SECOND_ASSET, 2), 12);
+            assert_ok!(Dex::do_try_state());
+        });
+    }
+
+    #[test]
+    fn depositing_and_withdrawing_one_asset_without_other_asset() {
+        new_test_ext().execute_with(|| {
+            create_assets();
+            init_pool(FIRST_ASSET, SECOND_ASSET);
+            assert_ok!(Assets::transfer(Origin::signed(1), 0, 2, 41));
+
+            // The swapped part is deposited in full, only the minimum balance of the deposited
+            // asset is kept by the operator
+            assert_ok!(Dex::deposit_one_asset(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                40,
+                1,
+                2,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 5);
+            assert_eq!(balance(SECOND_ASSET, 2), 0);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(2820));
+            assert_eq!(balance(FIRST_ASSET, 314159265), 86);
+            assert_eq!(balance(SECOND_ASSET, 314159265), 50);
+
+            // The withdrawn second asset is swapped back in full
+            assert_ok!(Dex::withdraw_one_asset(
+                Origin::signed(2),
+                314159265,
+                FIRST_ASSET,
+                10,
+                2,
+                None
+            ));
+            assert_eq!(balance(FIRST_ASSET, 2), 14);
+            assert_eq!(balance(SECOND_ASSET, 2), 0);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(1925));
+
+            assert_ok!(Dex::remove_liquidity_one_asset(
+                Origin::signed(2),
+                314159265,
+                1925,
+                FIRST_ASSET,
+                1,
+                2,
+                None
+            ));
+            assert!(balance(FIRST_ASSET, 2) > 14);
+            assert_eq!(balance(SECOND_ASSET, 2), 0);
+            assert_eq!(Dex::get_pool_share(314159265, 2), Some(0));
+            assert_ok!(Dex::do_try_state());
+        });
+    }
+}
@@ -0,0 +1,67 @@
+use sp_runtime::{DispatchError, DispatchResult};
+
+// A callback that is invoked in the middle of a flash swap
+pub trait OnFlashSwap<AccountId, AssetId, Balance> {
+    // Called after `amount_out` of `asset_out` has been transferred from the pool to `operator`
+    // Before returning, the pool has to receive enough of `asset_in` (or `asset_out` back)
+    // for the pool constant to hold, otherwise the whole flash swap is reverted
+    fn on_flash_swap(
+        operator: &AccountId,
+        pool: &AccountId,
+        asset_out: AssetId,
+        amount_out: Balance,
+        asset_in: AssetId,
+    ) -> DispatchResult;
+}
+
+// Flash swaps are disabled when there is no handler
+impl<AccountId, AssetId, Balance> OnFlashSwap<AccountId, AssetId, Balance> for () {
+    fn on_flash_swap(
+        _operator: &AccountId,
+        _pool: &AccountId,
+        _asset_out: AssetId,
+        _amount_out: Balance,
+        _asset_in: AssetId,
+    ) -> DispatchResult {
+        Err(DispatchError::Other("Flash swaps are disabled"))
+    }
+}
+
+// Single-asset flash loans of the pool reserves
+pub trait FlashLoan<AccountId, AssetId, Balance> {
+    // Lends `amount` of `asset` from `pool` to `borrower` and invokes the borrower callback
+    // The pool has to receive `amount` plus the fee back before the callback returns,
+    // otherwise all changes are reverted and an error is returned
+    fn flash_loan(
+        borrower: &AccountId,
+        pool: &AccountId,
+        asset: AssetId,
+        amount: Balance,
+    ) -> DispatchResult;
+}
+
+// A callback that is invoked in the middle of a flash loan
+pub trait OnFlashLoan<AccountId, AssetId, Balance> {
+    // Called after `amount` of `asset` has been transferred from the pool to `borrower`
+    // Before returning, the pool has to receive `amount` plus `fee` of `asset`
+    fn on_flash_loan(
+        borrower: &AccountId,
+        pool: &AccountId,
+        asset: AssetId,
+        amount: Balance,
+        fee: Balance,
+    ) -> DispatchResult;
+}
+
+// Flash loans are disabled when there is no receiver
+impl<AccountId, AssetId, Balance> OnFlashLoan<AccountId, AssetId, Balance> for () {
+    fn on_flash_loan(
+        _borrower: &AccountId,
+        _pool: &AccountId,
+        _asset: AssetId,
+        _amount: Balance,
+        _fee: Balance,
+    ) -> DispatchResult {
+        Err(DispatchError::Other("Flash loans are disabled"))
+    }
+}
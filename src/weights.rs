@@ -0,0 +1,240 @@
+// Weights of the pallet extrinsics
+// The pallet has no benchmarks yet, so the computation part of every weight is a placeholder.
+// The storage part counts the reads and writes of the extrinsic, so it follows `DbWeight`
+
+use frame_support::sp_std::marker::PhantomData;
+use frame_support::{
+    traits::Get,
+    weights::{constants::RocksDbWeight, Weight},
+};
+
+// Reads of quoting one candidate: its `AssetPools` and `Pools` entries, both balances of
+// the pool and its dynamic fee
+const QUOTE_READS: u64 = 5;
+// Storage accesses of one swap, it is the same as `swap_token`
+const SWAP_READS: u64 = 12;
+const SWAP_WRITES: u64 = 7;
+
+pub trait WeightInfo {
+    fn init() -> Weight;
+    fn swap_token() -> Weight;
+    fn deposit() -> Weight;
+    fn withdraw() -> Weight;
+    fn deposit_one_asset() -> Weight;
+    fn withdraw_one_asset() -> Weight;
+    fn remove_liquidity_one_asset() -> Weight;
+    // Only the pallet part of the flash swap, `Config::FlashSwapHandler` is not included
+    fn flash_swap() -> Weight;
+    // `c` is the amount of quoted pools and `h` is the amount of hops
+    fn swap_exact_in_auto(c: u32, h: u32) -> Weight;
+    // `r` is the amount of routes and `h` is the amount of hops in a route
+    fn swap_split(r: u32, h: u32) -> Weight;
+    // The base weight of a batch of `n` operations, without the operations themselves
+    fn batch_liquidity(n: u32) -> Weight;
+    fn add_fee_tier() -> Weight;
+    fn remove_fee_tier() -> Weight;
+    fn list_asset() -> Weight;
+    fn delist_asset() -> Weight;
+    fn set_allow_list_enabled() -> Weight;
+    fn set_allow_death() -> Weight;
+    fn set_default_max_price_impact() -> Weight;
+    fn set_pool_max_price_impact() -> Weight;
+    fn set_dynamic_fee() -> Weight;
+    fn set_circuit_breaker_threshold() -> Weight;
+    fn freeze_pool() -> Weight;
+    fn thaw_pool() -> Weight;
+    // `n` is the amount of `PoolShares` entries removed and paid out by one call
+    fn destroy_pool(n: u32) -> Weight;
+}
+
+// Weights that use the database weights of the runtime
+pub struct SubstrateWeight<T>(PhantomData<T>);
+
+impl<T: frame_system::Config> WeightInfo for SubstrateWeight<T> {
+    fn init() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(10, 11))
+    }
+    fn swap_token() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(12, 7))
+    }
+    fn deposit() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(9, 7))
+    }
+    fn withdraw() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(8, 7))
+    }
+    fn deposit_one_asset() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(21, 14))
+    }
+    fn withdraw_one_asset() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(22, 16))
+    }
+    fn remove_liquidity_one_asset() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(20, 14))
+    }
+    fn flash_swap() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(12, 9))
+    }
+    fn swap_exact_in_auto(c: u32, h: u32) -> Weight {
+        (1000 as Weight)
+            .saturating_mul(c.saturating_add(h).into())
+            .saturating_add(T::DbWeight::get().reads(QUOTE_READS.saturating_mul(c.into())))
+            .saturating_add(
+                T::DbWeight::get()
+                    .reads_writes(SWAP_READS, SWAP_WRITES)
+                    .saturating_mul(h.into()),
+            )
+    }
+    fn swap_split(r: u32, h: u32) -> Weight {
+        let swaps: Weight = r.saturating_mul(h).into();
+        (1000 as Weight).saturating_mul(swaps).saturating_add(
+            T::DbWeight::get()
+                .reads_writes(SWAP_READS, SWAP_WRITES)
+                .saturating_mul(swaps),
+        )
+    }
+    fn batch_liquidity(n: u32) -> Weight {
+        (100 as Weight).saturating_mul(n.into())
+    }
+    fn add_fee_tier() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn remove_fee_tier() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn list_asset() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn delist_asset() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn set_allow_list_enabled() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().writes(1))
+    }
+    fn set_allow_death() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().writes(1))
+    }
+    fn set_default_max_price_impact() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().writes(1))
+    }
+    fn set_pool_max_price_impact() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn set_dynamic_fee() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn set_circuit_breaker_threshold() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().writes(1))
+    }
+    fn freeze_pool() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn thaw_pool() -> Weight {
+        (1000 as Weight).saturating_add(T::DbWeight::get().reads_writes(1, 1))
+    }
+    fn destroy_pool(n: u32) -> Weight {
+        (1000 as Weight)
+            .saturating_add((1000 as Weight).saturating_mul(n.into()))
+            .saturating_add(T::DbWeight::get().reads_writes(8, 14))
+            .saturating_add(
+                T::DbWeight::get()
+                    .reads_writes(5, 5)
+                    .saturating_mul(n.into()),
+            )
+    }
+}
+
+// Weights for tests and runtimes that do not set their own, with RocksDB database weights
+impl WeightInfo for () {
+    fn init() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(10, 11))
+    }
+    fn swap_token() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(12, 7))
+    }
+    fn deposit() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(9, 7))
+    }
+    fn withdraw() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(8, 7))
+    }
+    fn deposit_one_asset() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(21, 14))
+    }
+    fn withdraw_one_asset() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(22, 16))
+    }
+    fn remove_liquidity_one_asset() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(20, 14))
+    }
+    fn flash_swap() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(12, 9))
+    }
+    fn swap_exact_in_auto(c: u32, h: u32) -> Weight {
+        (1000 as Weight)
+            .saturating_mul(c.saturating_add(h).into())
+            .saturating_add(RocksDbWeight::get().reads(QUOTE_READS.saturating_mul(c.into())))
+            .saturating_add(
+                RocksDbWeight::get()
+                    .reads_writes(SWAP_READS, SWAP_WRITES)
+                    .saturating_mul(h.into()),
+            )
+    }
+    fn swap_split(r: u32, h: u32) -> Weight {
+        let swaps: Weight = r.saturating_mul(h).into();
+        (1000 as Weight).saturating_mul(swaps).saturating_add(
+            RocksDbWeight::get()
+                .reads_writes(SWAP_READS, SWAP_WRITES)
+                .saturating_mul(swaps),
+        )
+    }
+    fn batch_liquidity(n: u32) -> Weight {
+        (100 as Weight).saturating_mul(n.into())
+    }
+    fn add_fee_tier() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn remove_fee_tier() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn list_asset() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn delist_asset() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn set_allow_list_enabled() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().writes(1))
+    }
+    fn set_allow_death() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().writes(1))
+    }
+    fn set_default_max_price_impact() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().writes(1))
+    }
+    fn set_pool_max_price_impact() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn set_dynamic_fee() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn set_circuit_breaker_threshold() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().writes(1))
+    }
+    fn freeze_pool() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn thaw_pool() -> Weight {
+        (1000 as Weight).saturating_add(RocksDbWeight::get().reads_writes(1, 1))
+    }
+    fn destroy_pool(n: u32) -> Weight {
+        (1000 as Weight)
+            .saturating_add((1000 as Weight).saturating_mul(n.into()))
+            .saturating_add(RocksDbWeight::get().reads_writes(8, 14))
+            .saturating_add(
+                RocksDbWeight::get()
+                    .reads_writes(5, 5)
+                    .saturating_mul(n.into()),
+            )
+    }
+}